@@ -0,0 +1,225 @@
+use crate::fail;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone)]
+pub struct Architecture {
+    pub nb_qubits: usize,
+    pub coupling: Vec<Vec<bool>>,
+}
+
+impl Architecture {
+    pub fn new(nb_qubits: usize) -> Self {
+        Architecture {
+            nb_qubits,
+            coupling: vec![vec![false; nb_qubits]; nb_qubits],
+        }
+    }
+
+    // The file starts with the number of physical qubits, followed by one
+    // coupled pair "a b" per line. Lines starting with '#' are ignored.
+    pub fn from_file(filename: &str) -> Architecture {
+        let file = match File::open(filename) {
+            Ok(file) => file,
+            Err(_) => { fail!("Cannot open architecture file: {}", filename) },
+        };
+        let mut arch: Option<Architecture> = None;
+        for line in BufReader::new(file).lines() {
+            let line = line.unwrap();
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue }
+            let values: Vec<usize> = match line.split_whitespace().map(|s| s.parse()).collect() {
+                Ok(values) => values,
+                Err(_) => { fail!("Invalid line in architecture file: {}", line) },
+            };
+            match (&mut arch, values.len()) {
+                (None, 1) => { arch = Some(Architecture::new(values[0])); },
+                (Some(a), 2) if values[0] < a.nb_qubits && values[1] < a.nb_qubits && values[0] != values[1] => {
+                    a.add_edge(values[0], values[1]);
+                },
+                _ => { fail!("Invalid line in architecture file: {}", line) },
+            }
+        }
+        let arch = match arch {
+            Some(arch) => arch,
+            None => { fail!("Empty architecture file: {}", filename) },
+        };
+        if !arch.is_connected() {
+            fail!("The coupling graph of {} is not connected", filename);
+        }
+        arch
+    }
+
+    pub fn add_edge(&mut self, a: usize, b: usize) {
+        self.coupling[a][b] = true;
+        self.coupling[b][a] = true;
+    }
+
+    pub fn neighbors(&self, a: usize) -> Vec<usize> {
+        (0..self.nb_qubits).filter(|&b| self.coupling[a][b]).collect()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.nb_qubits == 0 || self.bfs_order(0).len() == self.nb_qubits
+    }
+
+    fn bfs_order(&self, root: usize) -> Vec<usize> {
+        let mut visited = vec![false; self.nb_qubits];
+        let mut order = vec![root];
+        visited[root] = true;
+        let mut i = 0;
+        while i < order.len() {
+            for b in self.neighbors(order[i]) {
+                if !visited[b] {
+                    visited[b] = true;
+                    order.push(b);
+                }
+            }
+            i += 1;
+        }
+        order
+    }
+
+    pub fn distances(&self) -> Vec<Vec<usize>> {
+        (0..self.nb_qubits).map(|a| {
+            let mut dist = vec![usize::MAX; self.nb_qubits];
+            dist[a] = 0;
+            for u in self.bfs_order(a) {
+                for v in self.neighbors(u) {
+                    if dist[v] == usize::MAX {
+                        dist[v] = dist[u] + 1;
+                    }
+                }
+            }
+            dist
+        }).collect()
+    }
+
+    pub fn shortest_path(&self, a: usize, b: usize, allowed: &[bool]) -> Option<Vec<usize>> {
+        let mut parent = vec![usize::MAX; self.nb_qubits];
+        let mut queue = VecDeque::new();
+        parent[a] = a;
+        queue.push_back(a);
+        while let Some(u) = queue.pop_front() {
+            if u == b {
+                let mut path = vec![b];
+                while *path.last().unwrap() != a {
+                    path.push(parent[*path.last().unwrap()]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for v in self.neighbors(u) {
+                if allowed[v] && parent[v] == usize::MAX {
+                    parent[v] = u;
+                    queue.push_back(v);
+                }
+            }
+        }
+        None
+    }
+
+    // Qubits sorted such that removing them in this order never disconnects
+    // the remaining coupling graph.
+    pub fn elimination_order(&self) -> Vec<usize> {
+        let mut order = self.bfs_order(0);
+        order.reverse();
+        order
+    }
+
+    // Approximate Steiner tree spanning the root and the terminals, restricted
+    // to the allowed qubits. Edges are (parent, child) pairs and every parent
+    // appears before its children.
+    pub fn steiner_tree(&self, root: usize, terminals: &[usize], allowed: &[bool]) -> Vec<(usize, usize)> {
+        let mut in_tree = vec![false; self.nb_qubits];
+        in_tree[root] = true;
+        let mut edges = Vec::new();
+        let mut remaining: Vec<usize> = terminals.iter().filter(|&&t| t != root).copied().collect();
+        while !remaining.is_empty() {
+            let mut parent = vec![usize::MAX; self.nb_qubits];
+            let mut queue = VecDeque::new();
+            for v in 0..self.nb_qubits {
+                if in_tree[v] {
+                    parent[v] = v;
+                    queue.push_back(v);
+                }
+            }
+            let mut target = None;
+            while let Some(u) = queue.pop_front() {
+                if remaining.contains(&u) {
+                    target = Some(u);
+                    break;
+                }
+                for v in self.neighbors(u) {
+                    if allowed[v] && parent[v] == usize::MAX {
+                        parent[v] = u;
+                        queue.push_back(v);
+                    }
+                }
+            }
+            let target = match target {
+                Some(target) => target,
+                None => { fail!("The coupling graph does not connect the required qubits") },
+            };
+            let mut path = vec![target];
+            while !in_tree[*path.last().unwrap()] {
+                path.push(parent[*path.last().unwrap()]);
+            }
+            for k in (1..path.len()).rev() {
+                edges.push((path[k], path[k - 1]));
+                in_tree[path[k - 1]] = true;
+            }
+            remaining.retain(|&t| !in_tree[t]);
+        }
+        edges
+    }
+
+    // CNOT network accumulating the parity of the terminals onto the root,
+    // using only coupled pairs. Qubits of the tree other than the root are left
+    // in a modified state, the network has to be reversed to restore them.
+    pub fn steiner_parity(&self, root: usize, terminals: &[usize], allowed: &[bool]) -> Vec<(String, Vec<usize>)> {
+        let edges = self.steiner_tree(root, terminals, allowed);
+        let mut children = vec![Vec::new(); self.nb_qubits];
+        let mut nodes = vec![root];
+        for (p, c) in &edges {
+            children[*p].push(*c);
+            nodes.push(*c);
+        }
+        let mut circ = Vec::new();
+        for &v in nodes.iter().rev() {
+            if children[v].is_empty() { continue }
+            let mut first = 0;
+            if !terminals.contains(&v) {
+                circ.push(("cx".to_string(), vec![v, children[v][0]]));
+                circ.push(("cx".to_string(), vec![children[v][0], v]));
+                first = 1;
+            }
+            for &c in &children[v][first..] {
+                circ.push(("cx".to_string(), vec![c, v]));
+            }
+        }
+        circ
+    }
+
+    // Row additions (from, to) along a Steiner tree turning the indicator
+    // vector of `ones` into the unit vector of the root.
+    pub fn steiner_reduce(&self, root: usize, ones: &[usize], allowed: &[bool]) -> Vec<(usize, usize)> {
+        let edges = self.steiner_tree(root, ones, allowed);
+        let mut values = vec![false; self.nb_qubits];
+        for &v in ones {
+            values[v] = true;
+        }
+        let mut ops = Vec::new();
+        for &(p, c) in edges.iter().rev() {
+            if !values[p] && values[c] {
+                ops.push((c, p));
+                values[p] = true;
+            }
+        }
+        for &(p, c) in edges.iter().rev() {
+            ops.push((p, c));
+        }
+        ops
+    }
+}
@@ -1,16 +1,62 @@
+use crate::fail;
 use regex::Regex;
 use std::fs::{File};
 use std::io::{BufRead, BufReader, Write};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use serde_json::{json, Value};
+use crate::bit_vector::BitVector;
 use crate::phase_polynomial::PhasePolynomial;
 use crate::tableau::TableauColumnMajor;
-use crate::t_opt::{tohpe, fast_todd};
+use crate::t_opt::PhasePolynomialOptimizer;
+use crate::architecture::Architecture;
+use crate::schedule::Schedule;
+use crate::peephole::peephole;
 
 #[derive(Debug, Clone)]
 pub struct Circuit {
     pub circ: Vec<(String, Vec<usize>)>,
     pub nb_qubits: usize,
-    pub ancillas: HashMap::<usize, usize>,
+    // Ancillas mapped to their parent, the qubit (or ancilla) whose wire is
+    // moved to them by the gadgetization of a Hadamard gate. The scratch
+    // ancillas, which start and end in the state |0>, have no parent.
+    pub ancillas: HashMap::<usize, Option<usize>>,
+}
+
+// Statistics of a circuit. The Hadamard-free slices are the maximal parts of
+// the circuit containing T gates and no Hadamard gate.
+#[derive(Debug, Clone)]
+pub struct CircuitStats {
+    pub qubits: usize,
+    pub ancillas: usize,
+    pub gates: usize,
+    pub histogram: BTreeMap<String, usize>,
+    pub t_count: usize,
+    pub h_count: usize,
+    pub internal_h_count: usize,
+    pub cnot_count: usize,
+    pub depth: usize,
+    pub t_depth: usize,
+    pub two_qubit_depth: usize,
+    pub hadamard_free_slices: usize,
+}
+
+impl CircuitStats {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "qubits": self.qubits,
+            "ancillas": self.ancillas,
+            "gates": self.gates,
+            "histogram": self.histogram,
+            "t_count": self.t_count,
+            "h_count": self.h_count,
+            "internal_h_count": self.internal_h_count,
+            "cnot_count": self.cnot_count,
+            "depth": self.depth,
+            "t_depth": self.t_depth,
+            "two_qubit_depth": self.two_qubit_depth,
+            "hadamard_free_slices": self.hadamard_free_slices,
+        })
+    }
 }
 
 impl Circuit {
@@ -23,17 +69,28 @@ impl Circuit {
     }
 
     pub fn from_qc(filename: &str) -> (Circuit, String, HashMap<usize, String>) {
-        let mut c = Circuit::new(0);
         let file = File::open(filename).unwrap();
-        let reader = BufReader::new(file);
+        Circuit::read_qc(BufReader::new(file))
+    }
+
+    pub fn read_qc<R: BufRead>(reader: R) -> (Circuit, String, HashMap<usize, String>) {
+        let mut c = Circuit::new(0);
         let re = Regex::new(r"\s([[:alnum:]]*)").unwrap();
         let re_gate = Regex::new(r"(\.*[[:alpha:]]+\*?)\s").unwrap();
         let mut header: String = "".to_string();
         let mut qubits_mapping = HashMap::new();
         let mut rev_qubits_mapping = HashMap::new();
         for (_, line) in reader.lines().enumerate() {
-            let line = line.unwrap(); 
+            let mut line = line.unwrap(); 
             if line.len() == 0 || line.chars().next().unwrap() == '#' { continue }
+            let mut condition = None;
+            if line.starts_with("if ") {
+                let tokens: Vec<&str> = line.split_whitespace().collect();
+                if tokens.len() < 3 { fail!("Invalid classically controlled gate: {}", line) }
+                condition = Some(*qubits_mapping.get(tokens[1]).unwrap_or_else(|| fail!("Unknown qubit: {}", tokens[1])));
+                line = tokens[2..].join(" ");
+            }
+            let start = c.circ.len();
             let mut gate: Vec<String> = re_gate.captures_iter(&line).map(|x| x.get(1).unwrap().as_str().parse().unwrap()).collect();
             if gate.len() == 0 { continue }
             if gate[0] == ".v" {
@@ -48,7 +105,8 @@ impl Circuit {
                 header.push_str("\n"); 
                 continue
             }
-            let qubits: Vec<usize> = re.captures_iter(&line).map(|x| *qubits_mapping.get(x.get(1).unwrap().as_str()).unwrap()).collect();
+            let qubits: Vec<usize> = re.captures_iter(&line).map(|x| x.get(1).unwrap().as_str())
+                .map(|q| *qubits_mapping.get(q).unwrap_or_else(|| fail!("Unknown qubit: {}", q))).collect();
             if gate[0] == "tof" && qubits.len() == 3 { gate[0] = "tof".to_string() }
             else if (gate[0] == "Zd" || gate[0] == "Z") && qubits.len() == 3 { gate[0] = "ccz".to_string() }
             else if gate[0] == "cnot" || (gate[0] == "tof" && qubits.len() == 2) { gate[0] = "cx".to_string() }
@@ -61,17 +119,119 @@ impl Circuit {
                 gate[0] = "s".to_string();
             }
             else if gate[0] == "T" && qubits.len() == 1 { gate[0] = "t".to_string() }
+            else if (gate[0] == "measure" || gate[0] == "reset") && qubits.len() == 1 && condition.is_none() {}
             else if gate[0] == "T*" && qubits.len() == 1 { 
                 c.circ.push(("z".to_string(), qubits.clone()));
                 c.circ.push(("s".to_string(), qubits.clone()));
                 gate[0] = "t".to_string();
             }
-            else { println!("Operator not implemented: {}", gate[0]); std::process::exit(1) }
+            else { fail!("Operator not implemented: {}", gate[0]) }
             c.circ.push((gate[0].to_string(), qubits));
+            if let Some(condition) = condition {
+                for (gate, qubits) in c.circ[start..].iter_mut() {
+                    *gate = format!("if_{}", gate);
+                    qubits.insert(0, condition);
+                }
+            }
         }
         (c, header, rev_qubits_mapping)
     }
 
+    // Reads a circuit in the OpenQASM 2.0 format. The qubits of successive
+    // registers are numbered consecutively and named after their register and
+    // index in the returned header. Gates can only be conditioned on one-bit
+    // classical registers, by the last measurement written into them.
+    pub fn read_qasm(text: &str) -> (Circuit, String, HashMap<usize, String>) {
+        let mut c = Circuit::new(0);
+        let mut registers = HashMap::new();
+        let mut classical_registers = HashMap::new();
+        let mut measured = HashMap::new();
+        let mut names = HashMap::new();
+        let text: Vec<&str> = text.lines().map(|line| line.split("//").next().unwrap()).collect();
+        for statement in text.join("\n").split(';') {
+            let mut statement = statement.trim();
+            if statement.is_empty() || statement.starts_with("OPENQASM") || statement.starts_with("include") { continue }
+            let mut condition = None;
+            if let Some(rest) = statement.strip_prefix("if") {
+                let (test, rest) = match rest.trim_start().strip_prefix('(').and_then(|rest| rest.split_once(')')) {
+                    Some(split) => split,
+                    None => { fail!("Invalid condition: {}", statement) },
+                };
+                let qubit = match test.split_once("==") {
+                    Some((register, "1")) if classical_registers.get(register.trim()) == Some(&1) => measured.get(register.trim()),
+                    _ => None,
+                };
+                condition = match qubit {
+                    Some(&qubit) => Some(qubit),
+                    None => { fail!("Condition not implemented: {}", test) },
+                };
+                statement = rest.trim();
+            }
+            let (gate, operands) = statement.split_once(char::is_whitespace).unwrap_or((statement, ""));
+            let operands: Vec<&str> = operands.split([',', ' ']).filter(|o| !o.is_empty() && *o != "->").collect();
+            let register = |operand: &str| match operand.strip_suffix(']').and_then(|o| o.split_once('[')) {
+                Some((name, index)) => match index.parse::<usize>() {
+                    Ok(index) => (name.to_string(), index),
+                    Err(_) => { fail!("Invalid operand: {}", operand) },
+                },
+                None => { fail!("Operand not implemented: {}", operand) },
+            };
+            match gate {
+                "qreg" => {
+                    let (name, size) = register(operands[0]);
+                    registers.insert(name.to_string(), c.nb_qubits);
+                    for i in 0..size { names.insert(c.nb_qubits + i, format!("{}{}", name, i)); }
+                    c.nb_qubits += size;
+                    continue
+                },
+                "creg" => {
+                    let (name, size) = register(operands[0]);
+                    classical_registers.insert(name, size);
+                    continue
+                },
+                "barrier" => continue,
+                _ => {},
+            }
+            let mut qubits = Vec::new();
+            for operand in &operands {
+                let (name, index) = register(operand);
+                match registers.get(&name) {
+                    Some(offset) => qubits.push(offset + index),
+                    None if gate == "measure" => { measured.insert(name, *qubits.last().unwrap()); },
+                    None => { fail!("Register not declared: {}", name) },
+                }
+            }
+            let start = c.circ.len();
+            let gates: Vec<(&str, Vec<usize>)> = match (gate, qubits.len()) {
+                ("h" | "x" | "z" | "s" | "t", 1) => vec![(gate, qubits)],
+                ("sdg", 1) => vec![("z", qubits.clone()), ("s", qubits)],
+                ("tdg", 1) => vec![("z", qubits.clone()), ("s", qubits.clone()), ("t", qubits)],
+                ("cx", 2) => vec![("cx", qubits)],
+                ("cz", 2) => vec![("h", vec![qubits[1]]), ("cx", qubits.clone()), ("h", vec![qubits[1]])],
+                ("swap", 2) => vec![("cx", qubits.clone()), ("cx", vec![qubits[1], qubits[0]]), ("cx", qubits)],
+                ("ccx", 3) => vec![("tof", qubits)],
+                ("measure" | "reset", 1) if condition.is_none() => vec![(gate, qubits)],
+                _ => { fail!("Operator not implemented: {}", gate) },
+            };
+            for (gate, qubits) in gates { c.circ.push((gate.to_string(), qubits)); }
+            if let Some(condition) = condition {
+                for (gate, qubits) in c.circ[start..].iter_mut() {
+                    *gate = format!("if_{}", gate);
+                    qubits.insert(0, condition);
+                }
+            }
+        }
+        let header = format!(".v {}\n", (0..c.nb_qubits).map(|q| names[&q].to_string()).collect::<Vec<_>>().join(" "));
+        (c, header, names)
+    }
+
+    // Format of a circuit, "qasm" if its first statement is an OpenQASM
+    // version and "qc" otherwise.
+    pub fn detect_format(text: &str) -> &'static str {
+        let first = text.lines().map(|line| line.trim()).find(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with("//"));
+        if first.is_some_and(|line| line.starts_with("OPENQASM")) { "qasm" } else { "qc" }
+    }
+
     pub fn decompose_tof(&self) -> Circuit {
         let mut c = Circuit::new(self.nb_qubits);
         for (gate, qubits) in &self.circ {
@@ -110,40 +270,217 @@ impl Circuit {
         c
     }
 
-    pub fn to_qc(&self, filename: &str, header: String, mut map: HashMap<usize, String>) {
-        let mut file = File::create(filename).unwrap();
-        let mut index = map.len();
+    pub fn to_qc(&self, filename: &str, header: String, map: HashMap<usize, String>) {
+        self.write_qc(&mut File::create(filename).unwrap(), header, map);
+    }
+
+    pub fn write_qc<W: Write>(&self, file: &mut W, header: String, map: HashMap<usize, String>) {
+        self.write_qc_with(file, header, map, false);
+    }
+
+    // Writes the circuit in the .qc format, with a comment before each time step
+    // of the circuit when `moments` is true.
+    pub fn write_qc_with<W: Write>(&self, file: &mut W, header: String, mut map: HashMap<usize, String>, moments: bool) {
         let mut val = map.len();
         for s in header.split("\n") {
             write!(file, "{}", s).unwrap();
             let s2 = s.split(" ").collect::<Vec<_>>();
             if s2[0] == ".v"  {
-                for _ in self.ancillas.keys() {
+                for index in 0..self.nb_qubits {
+                    if map.contains_key(&index) { continue; }
                     while map.values().any(|x| *x == val.to_string()) {
                         val += 1;
                     }
                     write!(file, " {}", val).unwrap();
                     map.insert(index, val.to_string());
-                    index += 1;
                 }
             }
             write!(file, "\n").unwrap();
         }
         write!(file, "BEGIN\n").unwrap();
+        self.write_gates(file, "#", moments, |gate, q| Circuit::qc_gate(gate, q, &map));
+        write!(file, "END").unwrap();
+    }
+
+     fn qc_gate(gate: &str, q: &[usize], map: &HashMap<usize, String>) -> String {
+        match gate {
+            "h" => format!("H {}", map.get(&q[0]).unwrap()),
+            "x" => format!("X {}", map.get(&q[0]).unwrap()),
+            "z" => format!("Z {}", map.get(&q[0]).unwrap()),
+            "s" => format!("S {}", map.get(&q[0]).unwrap()),
+            "t" => format!("T {}", map.get(&q[0]).unwrap()),
+            "cx" => format!("cnot {} {}", map.get(&q[0]).unwrap(), map.get(&q[1]).unwrap()),
+            "ccx" | "tof" => format!("tof {} {} {}", map.get(&q[0]).unwrap(), map.get(&q[1]).unwrap(), map.get(&q[2]).unwrap()),
+            "ccz" => format!("Z {} {} {}", map.get(&q[0]).unwrap(), map.get(&q[1]).unwrap(), map.get(&q[2]).unwrap()),
+            "measure" => format!("measure {}", map.get(&q[0]).unwrap()),
+            "reset" => format!("reset {}", map.get(&q[0]).unwrap()),
+            _ if gate.starts_with("if_") => format!("if {} {}", map.get(&q[0]).unwrap(), Circuit::qc_gate(&gate[3..], &q[1..], map)),
+            _ => {fail!("Operator not implemented: {}", gate)},
+        }
+    }
+
+     fn qasm_gate(gate: &str, q: &[usize]) -> String {
+        match gate {
+            "h" | "x" | "z" | "s" | "t" => format!("{} q[{}];", gate, q[0]),
+            "cx" => format!("cx q[{}],q[{}];", q[0], q[1]),
+            "ccx" | "tof" => format!("ccx q[{}],q[{}],q[{}];", q[0], q[1], q[2]),
+            "ccz" => format!("h q[{2}];\nccx q[{0}],q[{1}],q[{2}];\nh q[{2}];", q[0], q[1], q[2]),
+            "measure" => format!("measure q[{0}] -> c{0}[0];", q[0]),
+            "reset" => format!("reset q[{}];", q[0]),
+            _ if gate.starts_with("if_") => Circuit::qasm_gate(&gate[3..], &q[1..]).split('\n')
+                .map(|line| format!("if(c{}==1) {}", q[0], line)).collect::<Vec<_>>().join("\n"),
+            _ => {fail!("Operator not implemented: {}", gate)},
+        }
+    }
+
+    // Measured qubits get their own one-bit classical register, as OpenQASM 2
+    // can only condition gates on whole registers.
+    pub fn to_qasm(&self, filename: &str) {
+        self.write_qasm(&mut File::create(filename).unwrap());
+    }
+
+    pub fn write_qasm<W: Write>(&self, file: &mut W) {
+        self.write_qasm_with(file, false);
+    }
+
+    pub fn write_qasm_with<W: Write>(&self, file: &mut W, moments: bool) {
+        write!(file, "OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[{}];\n", self.nb_qubits).unwrap();
+        let measured: BTreeSet<usize> = self.circ.iter().filter(|(gate, _)| gate == "measure").map(|(_, q)| q[0]).collect();
+        for q in measured {
+            writeln!(file, "creg c{}[1];", q).unwrap();
+        }
+        self.write_gates(file, "//", moments, Circuit::qasm_gate);
+    }
+
+     fn write_gates<W: Write, F: Fn(&str, &[usize]) -> String>(&self, file: &mut W, comment: &str, moments: bool, format_gate: F) {
+        if !moments {
+            for (gate, q) in &self.circ {
+                writeln!(file, "{}", format_gate(gate, q)).unwrap();
+            }
+            return;
+        }
+        for (i, moment) in Schedule::asap(self).moments.iter().enumerate() {
+            writeln!(file, "{} Moment {}", comment, i + 1).unwrap();
+            for (gate, q) in moment {
+                writeln!(file, "{}", format_gate(gate, q)).unwrap();
+            }
+        }
+    }
+
+    // Qubits listed on the .i and .o lines of the header, all the qubits being
+    // inputs (or outputs) when the corresponding line is missing.
+    pub fn io_qubits(&self, header: &str, map: &HashMap<usize, String>) -> (Vec<bool>, Vec<bool>) {
+        let mut inputs = vec![true; self.nb_qubits];
+        let mut outputs = vec![true; self.nb_qubits];
+        for line in header.split("\n") {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let io = match tokens.first() {
+                Some(&".i") => &mut inputs,
+                Some(&".o") => &mut outputs,
+                _ => continue,
+            };
+            for (q, b) in io.iter_mut().enumerate() {
+                *b = map.get(&q).is_some_and(|name| tokens[1..].contains(&name.as_str()));
+            }
+        }
+        (inputs, outputs)
+    }
+
+    // Removes the gates whose action is known when the qubits which are not
+    // inputs are initialized in the state |0>. As long as a qubit is in a
+    // computational basis state, its value is tracked classically: diagonal
+    // gates acting on it only add a global phase, and it can be removed from
+    // the controls of CNOT and Toffoli gates.
+    pub fn propagate_constants(&self, inputs: &[bool]) -> Circuit {
+        let mut c = Circuit::new(self.nb_qubits);
+        c.ancillas = self.ancillas.clone();
+        let mut value: Vec<Option<bool>> = (0..self.nb_qubits).map(|q| if inputs[q] { None } else { Some(false) }).collect();
+        fn release(c: &mut Circuit, value: &mut [Option<bool>], q: usize) {
+            if value[q] == Some(true) { c.circ.push(("x".to_string(), vec![q])); }
+            value[q] = None;
+        }
         for (gate, q) in &self.circ {
-            match &gate[..] {
-                "h" => write!(file, "H {}\n", map.get(&q[0]).unwrap()).unwrap(),
-                "x" => write!(file, "X {}\n", map.get(&q[0]).unwrap()).unwrap(),
-                "z" => write!(file, "Z {}\n", map.get(&q[0]).unwrap()).unwrap(),
-                "s" => write!(file, "S {}\n", map.get(&q[0]).unwrap()).unwrap(),
-                "t" => write!(file, "T {}\n", map.get(&q[0]).unwrap()).unwrap(),
-                "cx" => write!(file, "cnot {} {}\n", map.get(&q[0]).unwrap(), map.get(&q[1]).unwrap()).unwrap(),
-                "ccx" => write!(file, "tof {} {} {}\n", map.get(&q[0]).unwrap(), map.get(&q[1]).unwrap(), map.get(&q[2]).unwrap()).unwrap(),
-                "ccz" => write!(file, "Z {} {} {}\n", map.get(&q[0]).unwrap(), map.get(&q[1]).unwrap(), map.get(&q[2]).unwrap()).unwrap(),
-                _ => {println!("Operator not implemented: {}", gate); std::process::exit(1)},
+            let gate = if gate == "tof" && q.len() == 2 { "cx" } else { &gate[..] };
+            match gate {
+                "x" if value[q[0]].is_some() => { value[q[0]] = value[q[0]].map(|b| !b); },
+                "z" | "s" | "t" if value[q[0]].is_some() => {},
+                "reset" if value[q[0]].is_some() => { value[q[0]] = Some(false); },
+                "reset" => { c.circ.push((gate.to_string(), q.to_vec())); value[q[0]] = Some(false); },
+                "cx" | "tof" | "ccz" => {
+                    let (controls, target) = if gate == "ccz" { (&q[..], None) } else { (&q[..q.len() - 1], q.last()) };
+                    if controls.iter().any(|&a| value[a] == Some(false)) { continue; }
+                    let controls: Vec<usize> = controls.iter().copied().filter(|&a| value[a].is_none()).collect();
+                    match (controls.len(), target) {
+                        (0, Some(&t)) if value[t].is_some() => { value[t] = value[t].map(|b| !b); },
+                        (0, Some(&t)) => { c.circ.push(("x".to_string(), vec![t])); },
+                        (1, Some(&t)) => { release(&mut c, &mut value, t); c.circ.push(("cx".to_string(), vec![controls[0], t])); },
+                        (0, None) => {},
+                        (1, None) => { c.circ.push(("z".to_string(), controls)); },
+                        _ => {
+                            for &a in q { release(&mut c, &mut value, a); }
+                            c.circ.push((gate.to_string(), q.to_vec()));
+                        },
+                    }
+                },
+                _ => {
+                    for &a in q { release(&mut c, &mut value, a); }
+                    c.circ.push((gate.to_string(), q.to_vec()));
+                },
             }
         }
-        write!(file, "END").unwrap();
+        for q in 0..self.nb_qubits {
+            release(&mut c, &mut value, q);
+        }
+        c
+    }
+
+    // Removes the gates acting only on qubits which are discarded at the end of
+    // the circuit, i.e. which are not outputs and are not used afterwards by a
+    // gate acting on an output qubit.
+    pub fn remove_discarded(&self, outputs: &[bool]) -> Circuit {
+        let mut c = Circuit::new(self.nb_qubits);
+        c.ancillas = self.ancillas.clone();
+        let mut discarded: Vec<bool> = (0..self.nb_qubits).map(|q| !outputs.get(q).copied().unwrap_or(true)).collect();
+        for (gate, q) in self.circ.iter().rev() {
+            if !Circuit::is_barrier(gate) && q.iter().all(|&a| discarded[a]) { continue; }
+            for &a in q { discarded[a] = false; }
+            c.circ.push((gate.to_string(), q.to_vec()));
+        }
+        c.circ.reverse();
+        c
+    }
+
+    // Measurements, resets and classically controlled gates, across which the
+    // circuit is not optimized.
+    pub fn is_barrier(gate: &str) -> bool {
+        gate == "measure" || gate == "reset" || gate.starts_with("if_")
+    }
+
+    pub fn has_barriers(&self) -> bool {
+        self.circ.iter().any(|(gate, _)| Circuit::is_barrier(gate))
+    }
+
+    // Applies the pass to each maximal unitary part of the circuit, keeping the
+    // measurements, resets and classically controlled gates in between.
+    pub fn map_unitary_parts<F: FnMut(Circuit) -> Circuit>(&self, mut pass: F) -> Circuit {
+        let mut c = Circuit::new(self.nb_qubits);
+        c.ancillas = self.ancillas.clone();
+        let mut part = Circuit::new(self.nb_qubits);
+        for (gate, q) in &self.circ {
+            if !Circuit::is_barrier(gate) {
+                part.circ.push((gate.to_string(), q.to_vec()));
+                continue
+            }
+            if !part.circ.is_empty() {
+                c.append(pass(part).circ);
+                part = Circuit::new(self.nb_qubits);
+            }
+            c.circ.push((gate.to_string(), q.to_vec()));
+        }
+        if !part.circ.is_empty() {
+            c.append(pass(part).circ);
+        }
+        c
     }
 
     pub fn get_statistics(&self) -> (usize, usize, usize) {
@@ -167,25 +504,248 @@ impl Circuit {
         (h_count, internal_h_count, t_count)
     }
 
+    pub fn stats(&self) -> CircuitStats {
+        let (h_count, internal_h_count, t_count) = self.get_statistics();
+        let mut histogram = BTreeMap::new();
+        let mut t_layers = vec![0; self.nb_qubits];
+        let mut two_qubit_layers = vec![0; self.nb_qubits];
+        let mut hadamard_free_slices = 0;
+        let mut new_slice = true;
+        for (gate, qubits) in &self.circ {
+            *histogram.entry(gate.to_string()).or_insert(0) += 1;
+            let quantum_qubits = if gate.starts_with("if_") { qubits.len() - 1 } else { qubits.len() };
+            let t_layer = qubits.iter().map(|&q| t_layers[q]).max().unwrap() + (gate == "t") as usize;
+            let two_qubit_layer = qubits.iter().map(|&q| two_qubit_layers[q]).max().unwrap() + (quantum_qubits > 1) as usize;
+            for &q in qubits {
+                t_layers[q] = t_layer;
+                two_qubit_layers[q] = two_qubit_layer;
+            }
+            if gate == "h" || Circuit::is_barrier(gate) { new_slice = true; }
+            if gate == "t" && new_slice {
+                hadamard_free_slices += 1;
+                new_slice = false;
+            }
+        }
+        CircuitStats {
+            qubits: self.nb_qubits,
+            ancillas: self.ancillas.len(),
+            gates: self.circ.len(),
+            histogram,
+            t_count,
+            h_count,
+            internal_h_count,
+            cnot_count: self.cnot_count(),
+            depth: self.depth(),
+            t_depth: t_layers.into_iter().max().unwrap_or(0),
+            two_qubit_depth: two_qubit_layers.into_iter().max().unwrap_or(0),
+            hadamard_free_slices,
+        }
+    }
+
+    // Statistics of the circuit where the qubits which are not inputs, being
+    // initialized in the state |0>, are counted as ancillas.
+    pub fn stats_with_inputs(&self, inputs: &[bool]) -> CircuitStats {
+        let mut stats = self.stats();
+        stats.ancillas += (0..inputs.len()).filter(|&q| !inputs[q] && !self.ancillas.contains_key(&q)).count();
+        stats
+    }
+
+    pub fn cnot_count(&self) -> usize {
+        self.circ.iter().filter(|(gate, _)| gate == "cx").count()
+    }
+
+    pub fn depth(&self) -> usize {
+        let mut layers = vec![0; self.nb_qubits];
+        for (_, qubits) in &self.circ {
+            let layer = qubits.iter().map(|&q| layers[q]).max().unwrap() + 1;
+            for &q in qubits {
+                layers[q] = layer;
+            }
+        }
+        layers.into_iter().max().unwrap_or(0)
+    }
+
     pub fn append(&mut self, mut circ: Vec<(String, Vec<usize>)>) {
         self.circ.append(&mut circ);
     }
 
     pub fn hadamard_gadgetization(&self) -> Circuit {
+        let mut flag = false;
+        let mut later_t = vec![false; self.circ.len()];
+        for (i, (gate, _)) in self.circ.iter().enumerate().rev() {
+            if Circuit::is_barrier(gate) { flag = false; }
+            later_t[i] = flag;
+            if gate == "t" { flag = true; }
+        }
+        let mut selected = vec![false; self.circ.len()];
+        flag = false;
+        for (i, (gate, _)) in self.circ.iter().enumerate() {
+            if gate == "t" { flag = true; }
+            if Circuit::is_barrier(gate) { flag = false; }
+            selected[i] = gate == "h" && later_t[i] && flag;
+        }
+        self.gadgetize(&selected)
+    }
+
+    // Gadgetizes at most `budget` internal Hadamard gates. Hadamard gates
+    // separating two phase polynomial slices are gadgetized group by group,
+    // merging first the slices with the most T gates per ancilla spent.
+    pub fn hadamard_gadgetization_budget(&self, budget: usize) -> Circuit {
+        let mut sizes = Vec::new();
+        let mut boundaries: Vec<Option<Vec<usize>>> = Vec::new();
+        let mut group = Vec::new();
+        let mut barrier = false;
+        for (i, (gate, _)) in self.circ.iter().enumerate() {
+            if gate == "t" {
+                if sizes.is_empty() || !group.is_empty() || barrier {
+                    if !sizes.is_empty() { boundaries.push(if barrier { None } else { Some(group) }); }
+                    sizes.push(0);
+                    group = Vec::new();
+                    barrier = false;
+                }
+                *sizes.last_mut().unwrap() += 1;
+            }
+            if gate == "h" && !sizes.is_empty() { group.push(i); }
+            if Circuit::is_barrier(gate) && !sizes.is_empty() { barrier = true; }
+        }
+        let mut selected = vec![false; self.circ.len()];
+        let mut remaining = budget;
+        loop {
+            let mut best: Option<(usize, usize)> = None;
+            for k in 0..boundaries.len() {
+                let cost = match &boundaries[k] {
+                    Some(group) if group.len() <= remaining => group.len(),
+                    _ => continue,
+                };
+                let gain = sizes[k] + sizes[k + 1];
+                if let Some((b, best_cost)) = best {
+                    let best_gain = sizes[b] + sizes[b + 1];
+                    if gain * best_cost <= best_gain * cost { continue; }
+                }
+                best = Some((k, cost));
+            }
+            let (k, cost) = match best {
+                Some(best) => best,
+                None => break,
+            };
+            for &i in boundaries[k].as_ref().unwrap() {
+                selected[i] = true;
+            }
+            remaining -= cost;
+            sizes[k] += sizes[k + 1];
+            sizes.remove(k + 1);
+            boundaries.remove(k);
+        }
+        self.gadgetize(&selected)
+    }
+
+    // Gadgetizes all internal Hadamard gates, but instead of postselecting the
+    // ancillas in the state |+>, they are measured in the X basis at the end of
+    // the circuit. The second circuit, to be appended once the first one has
+    // been optimized, contains these measurements and the corrections.
+    pub fn hadamard_gadgetization_measured(&self) -> (Circuit, Circuit) {
+        if self.has_barriers() {
+            fail!("Measured gadgets are not supported on circuits with measurements or classically controlled gates");
+        }
+        let c = self.hadamard_gadgetization();
+        let corrections = c.feed_forward();
+        (c, corrections)
+    }
+
+    // An outcome 1 when measuring the ancilla of a gadget is equivalent to an X
+    // gate applied just after the gadget. It is propagated to the end of the
+    // Hadamard-free part of the circuit as an X Pauli operator followed by a
+    // diagonal Clifford operator, whose inverse is applied if the outcome is 1.
+    // The ancillas are measured in the order of the gadgets as the correction
+    // of a gadget can act on the ancillas of the following ones.
+     fn feed_forward(&self) -> Circuit {
+        let k = self.ancillas.len();
+        let body = &self.circ[k..self.circ.len() - k];
+        let last_t = body.iter().rposition(|(gate, _)| gate == "t").map_or(0, |i| i + 1);
+        let tail = &body[last_t..];
+        let mut tail_inverse = Vec::new();
+        for (gate, q) in tail.iter().rev() {
+            if gate == "s" { tail_inverse.push(("z".to_string(), q.to_vec())); }
+            tail_inverse.push((gate.to_string(), q.to_vec()));
+        }
+        let mut ancillas: Vec<usize> = self.ancillas.keys().copied().collect();
+        ancillas.sort();
+        let mut c = Circuit::new(self.nb_qubits);
+        for (i, a) in ancillas.iter().enumerate() {
+            let end = body.iter().rposition(|(_, q)| q.contains(a)).unwrap();
+            let mut pauli = vec![false; self.nb_qubits];
+            pauli[body[end].1[0]] = true;
+            let mut diagonal: Vec<(Vec<bool>, usize)> = Vec::new();
+            for (gate, q) in &body[end + 1..last_t] {
+                match &gate[..] {
+                    "cx" => {
+                        pauli[q[1]] ^= pauli[q[0]];
+                        for (parity, _) in diagonal.iter_mut() { parity[q[0]] ^= parity[q[1]]; }
+                    },
+                    "x" => {
+                        for (parity, angle) in diagonal.iter_mut() {
+                            if parity[q[0]] { *angle = (8 - *angle) % 8; }
+                        }
+                    },
+                    "z" => {},
+                    "s" | "t" => {
+                        if !pauli[q[0]] { continue; }
+                        let angle = if gate == "s" { 4 } else { 6 };
+                        match diagonal.iter_mut().find(|(parity, _)| parity.iter().enumerate().all(|(j, &b)| b == (j == q[0]))) {
+                            Some((_, a)) => { *a = (*a + angle) % 8; },
+                            None => {
+                                let mut parity = vec![false; self.nb_qubits];
+                                parity[q[0]] = true;
+                                diagonal.push((parity, angle));
+                            },
+                        }
+                    },
+                    _ => { fail!("Measured gadgets require all internal Hadamard gates to be gadgetized") },
+                }
+            }
+            diagonal.retain(|(_, angle)| *angle != 0);
+            let support: Vec<usize> = ancillas[i + 1..].iter().copied()
+                .filter(|&b| pauli[b] || diagonal.iter().any(|(parity, _)| parity[b])).collect();
+            let mut correction = tail_inverse.clone();
+            for &b in &support {
+                correction.push(("h".to_string(), vec![b]));
+            }
+            for (q, _) in pauli.iter().enumerate().filter(|(_, &b)| b) {
+                correction.push(("x".to_string(), vec![q]));
+            }
+            for (parity, angle) in &diagonal {
+                let qubits: Vec<usize> = (0..self.nb_qubits).filter(|&q| parity[q]).collect();
+                for &q in &qubits[1..] {
+                    correction.push(("cx".to_string(), vec![q, qubits[0]]));
+                }
+                let inverse = (8 - angle) / 2;
+                if inverse % 2 == 1 { correction.push(("s".to_string(), vec![qubits[0]])); }
+                if inverse >= 2 { correction.push(("z".to_string(), vec![qubits[0]])); }
+                for &q in qubits[1..].iter().rev() {
+                    correction.push(("cx".to_string(), vec![q, qubits[0]]));
+                }
+            }
+            for &b in &support {
+                correction.push(("h".to_string(), vec![b]));
+            }
+            correction.extend_from_slice(tail);
+            c.circ.push(("measure".to_string(), vec![*a]));
+            for (gate, q) in correction {
+                c.circ.push((format!("if_{}", gate), [vec![*a], q].concat()));
+            }
+        }
+        c
+    }
+
+     fn gadgetize(&self, selected: &[bool]) -> Circuit {
         let mut c = Circuit::new(self.nb_qubits);
         let mut anc = Circuit::new(self.nb_qubits);
-        let mut flag = false;
-        let mut last = 0;
         let mut parent_ancilla = Vec::new();
         for i in 0..self.nb_qubits {
             parent_ancilla.push(i);
         }
-        for (i, (gate, _)) in self.circ.clone().into_iter().enumerate() {
-            if gate == "t" { last = i; }
-        }
         for (i, (gate, qubits)) in self.circ.clone().into_iter().enumerate() {
-            if gate == "t" { flag = true; }
-            if gate == "h" && i < last && flag {
+            if selected[i] {
                 anc.circ.push(("h".to_string(), vec![anc.nb_qubits]));
                 c.circ.push(("s".to_string(), vec![anc.nb_qubits]));
                 c.circ.push(("s".to_string(), qubits.to_vec()));
@@ -194,7 +754,7 @@ impl Circuit {
                 c.circ.push(("z".to_string(), vec![anc.nb_qubits]));
                 c.circ.push(("cx".to_string(), vec![anc.nb_qubits, qubits[0]]));
                 c.circ.push(("cx".to_string(), vec![qubits[0], anc.nb_qubits]));
-                anc.ancillas.insert(anc.nb_qubits, parent_ancilla[qubits[0]]);
+                anc.ancillas.insert(anc.nb_qubits, Some(parent_ancilla[qubits[0]]));
                 parent_ancilla[qubits[0]] = anc.nb_qubits;
                 anc.nb_qubits += 1;
             }
@@ -208,8 +768,167 @@ impl Circuit {
         c_out
     }
 
-    pub fn t_opt(&self, optimizer: String) -> Circuit {
-        SlicedCircuit::from_circ(self).t_opt(optimizer)
+    // Gate indices of the first and last multi-qubit gates acting on each
+    // ancilla. The single-qubit gates applied to an ancilla before (or after)
+    // them can be moved next to them, so they do not extend its lifetime.
+    // Ancillas only acted on by single-qubit gates live at their first gate.
+    pub fn ancilla_lifetimes(&self) -> HashMap<usize, (usize, usize)> {
+        let mut lifetimes: HashMap<usize, (usize, usize)> = HashMap::new();
+        let mut first_gate = HashMap::new();
+        for (i, (_, qubits)) in self.circ.iter().enumerate() {
+            for q in qubits {
+                if !self.ancillas.contains_key(q) { continue; }
+                first_gate.entry(*q).or_insert(i);
+                if qubits.len() > 1 {
+                    lifetimes.entry(*q).or_insert((i, i)).1 = i;
+                }
+            }
+        }
+        for (q, i) in first_gate {
+            lifetimes.entry(q).or_insert((i, i));
+        }
+        lifetimes
+    }
+
+    // Reassigns the ancillas to as few qubits as possible: an ancilla can be
+    // reused once its lifetime is over. Before a qubit is reused, the previous
+    // ancilla is measured, the outcome being postselected on |0> as is done at
+    // the end of the circuit after the gadgetization, and the qubit is reset.
+    // The other qubits keep their relative order and come first, followed by
+    // the ancilla qubits. Each ancilla qubit keeps the parent of its last
+    // ancilla.
+    pub fn recycle_ancillas(&self) -> Circuit {
+        let lifetimes = self.ancilla_lifetimes();
+        let mut order: Vec<usize> = lifetimes.keys().copied().collect();
+        order.sort_by_key(|a| (lifetimes[a].0, *a));
+        let mut index = HashMap::new();
+        let mut nb_qubits = 0;
+        for q in 0..self.nb_qubits {
+            if !self.ancillas.contains_key(&q) {
+                index.insert(q, nb_qubits);
+                nb_qubits += 1;
+            }
+        }
+        let mut wire_end: Vec<usize> = Vec::new();
+        let mut wire_ancilla: Vec<usize> = Vec::new();
+        let mut reused = HashSet::new();
+        for a in &order {
+            let (start, end) = lifetimes[a];
+            let wire = match wire_end.iter().position(|&e| e < start) {
+                Some(wire) => { reused.insert(wire_ancilla[wire]); wire },
+                None => { wire_end.push(0); wire_ancilla.push(0); wire_end.len() - 1 },
+            };
+            wire_end[wire] = end;
+            wire_ancilla[wire] = *a;
+            index.insert(*a, nb_qubits + wire);
+        }
+        let mut c = Circuit::new(nb_qubits + wire_end.len());
+        for a in &order {
+            let parent = self.ancillas[a].map(|parent| *index.get(&parent).unwrap_or(&parent));
+            c.ancillas.insert(index[a], parent);
+        }
+        let mut pending: HashMap<usize, Vec<(String, Vec<usize>)>> = HashMap::new();
+        let mut trailing: HashMap<usize, Vec<(String, Vec<usize>)>> = HashMap::new();
+        for (i, (gate, qubits)) in self.circ.iter().enumerate() {
+            if qubits.len() > 1 { continue; }
+            if let Some(&(start, end)) = lifetimes.get(&qubits[0]) {
+                let renamed = (gate.to_string(), vec![index[&qubits[0]]]);
+                if i < start { pending.entry(qubits[0]).or_default().push(renamed); }
+                else if i > end { trailing.entry(qubits[0]).or_default().push(renamed); }
+            }
+        }
+        for (i, (gate, qubits)) in self.circ.iter().enumerate() {
+            if qubits.len() == 1 && lifetimes.get(&qubits[0]).is_some_and(|&(start, end)| i < start || i > end) { continue; }
+            for q in qubits {
+                if lifetimes.get(q).is_some_and(|&(start, _)| i == start) { c.append(pending.remove(q).unwrap_or_default()); }
+            }
+            c.circ.push((gate.to_string(), qubits.iter().map(|q| index[q]).collect()));
+            for q in qubits {
+                if lifetimes.get(q).is_none_or(|&(_, end)| i != end) { continue; }
+                c.append(trailing.remove(q).unwrap_or_default());
+                if reused.contains(q) {
+                    c.circ.push(("measure".to_string(), vec![index[q]]));
+                    c.circ.push(("reset".to_string(), vec![index[q]]));
+                }
+            }
+        }
+        c
+    }
+
+    pub fn t_opt(&self, optimizer: &dyn PhasePolynomialOptimizer) -> Circuit {
+        self.t_opt_with(optimizer, "Elimination")
+    }
+
+    pub fn t_opt_with(&self, optimizer: &dyn PhasePolynomialOptimizer, synthesis: &str) -> Circuit {
+        if self.has_barriers() {
+            return self.map_unitary_parts(|c| c.t_opt_with(optimizer, synthesis));
+        }
+        peephole(&SlicedCircuit::from_circ(self).t_opt_with(optimizer, synthesis))
+    }
+
+    // T-count optimization using the qubits which are not inputs, starting in
+    // the state |0>, and the qubits which are not outputs, whose final state is
+    // discarded. The circuit is only preserved on these inputs and outputs, and
+    // circuits with measurements are optimized without using them.
+    pub fn t_opt_io_aware(&self, optimizer: &dyn PhasePolynomialOptimizer, synthesis: &str, inputs: &[bool], outputs: &[bool]) -> Circuit {
+        if self.has_barriers() {
+            return self.t_opt_with(optimizer, synthesis);
+        }
+        peephole(&SlicedCircuit::from_circ(self).t_opt_io_aware(optimizer, synthesis, inputs, outputs)).remove_discarded(outputs)
+    }
+
+    // T-depth optimization: the terms of each phase polynomial are partitioned
+    // into layers synthesized in parallel, using `nb_ancillas` additional
+    // scratch ancillas to fit more terms in each layer. The T-count is only
+    // reduced by the peephole optimization merging T gates.
+    pub fn t_par(&self, nb_ancillas: usize, synthesis: &str) -> Circuit {
+        let mut c = self.map_unitary_parts(|c| peephole(&SlicedCircuit::from_circ(&c.decompose_tof()).t_par(nb_ancillas, synthesis)));
+        c.nb_qubits += nb_ancillas;
+        for a in self.nb_qubits..c.nb_qubits {
+            c.ancillas.insert(a, None);
+        }
+        c
+    }
+
+    // T-count optimization up to a final diagonal operator, for circuits whose
+    // outputs are measured in the computational basis.
+    pub fn t_opt_measured(&self, optimizer: &dyn PhasePolynomialOptimizer, synthesis: &str) -> Circuit {
+        let split = self.circ.iter().rposition(|(gate, _)| Circuit::is_barrier(gate)).map_or(0, |i| i + 1);
+        let mut prefix = Circuit::new(self.nb_qubits);
+        prefix.ancillas = self.ancillas.clone();
+        prefix.circ = self.circ[..split].to_vec();
+        let mut suffix = prefix.clone();
+        suffix.circ = self.circ[split..].to_vec();
+        let mut c = if split > 0 { prefix.t_opt_with(optimizer, synthesis) } else { prefix };
+        let mut sliced = SlicedCircuit::from_circ(&suffix);
+        sliced.drop_final_diagonal();
+        c.append(sliced.t_opt_with(optimizer, synthesis).circ);
+        peephole(&c).remove_final_diagonal()
+    }
+
+    // Removes the diagonal gates which are not followed by any other gate
+    // acting on their qubits.
+    pub fn remove_final_diagonal(&self) -> Circuit {
+        let mut c = Circuit::new(self.nb_qubits);
+        c.ancillas = self.ancillas.clone();
+        let mut last = vec![true; self.nb_qubits];
+        for (gate, q) in self.circ.iter().rev() {
+            let diagonal = matches!(&gate[..], "z" | "s" | "t" | "ccz");
+            if diagonal && q.iter().all(|&a| last[a]) { continue; }
+            if !diagonal {
+                for &a in q { last[a] = false; }
+            }
+            c.circ.push((gate.to_string(), q.to_vec()));
+        }
+        c.circ.reverse();
+        c
+    }
+
+    pub fn t_opt_arch(&self, optimizer: &dyn PhasePolynomialOptimizer, arch: &Architecture) -> Circuit {
+        if self.has_barriers() {
+            return self.map_unitary_parts(|c| c.t_opt_arch(optimizer, arch));
+        }
+        peephole(&SlicedCircuit::from_circ(self).t_opt_arch(optimizer, arch))
     }
 }
 
@@ -270,7 +989,7 @@ impl SlicedCircuit {
                         tab.prepend_z(q[0]);
                     }
                 },
-                _ => {println!("Operator not implemented: {}", gate); std::process::exit(1)},
+                _ => {fail!("Operator not implemented: {}", gate)},
             }
         }
         if p.table.len() > 0 {
@@ -280,25 +999,188 @@ impl SlicedCircuit {
         sliced_c
     }
 
-    pub fn t_opt(&mut self, optimizer: String) -> Circuit {
+    // When the outputs are measured in the computational basis, the terms of
+    // the last phase polynomial which are mapped to diagonal operators by the
+    // final Clifford operator have no effect and are removed.
+    pub fn drop_final_diagonal(&mut self) {
+        if self.phase_polynomials.is_empty() { return; }
+        let last = self.phase_polynomials.len() - 1;
+        let basis = match self.tableau_vec.get(last) {
+            Some(tab) => tab.diagonal_parities(),
+            None => TableauColumnMajor::new(self.nb_qubits).diagonal_parities(),
+        };
+        self.phase_polynomials[last].table.retain(|parity| {
+            let mut parity = parity.clone();
+            for b in &basis {
+                if parity.get(b.get_first_one()) { parity.xor(b); }
+            }
+            parity.popcount() != 0
+        });
+    }
+
+    pub fn t_opt(&mut self, optimizer: &dyn PhasePolynomialOptimizer) -> Circuit {
+        self.t_opt_with(optimizer, "Elimination")
+    }
+
+    pub fn t_opt_with(&mut self, optimizer: &dyn PhasePolynomialOptimizer, synthesis: &str) -> Circuit {
         let mut c = self.init_circuit.clone();
         for i in 0..self.phase_polynomials.len() {
             let table = self.phase_polynomials[i].table.clone();
-            if optimizer == "FastTODD" {
-                self.phase_polynomials[i].table = fast_todd(table.clone(), self.nb_qubits);
+            self.phase_polynomials[i].table = optimizer.optimize(table.clone(), self.nb_qubits);
+            c.append(self.phase_polynomials[i].clifford_correction(&table, self.nb_qubits).to_circ_with(false, synthesis).circ);
+            c.append(self.phase_polynomials[i].to_circ().circ);
+            if self.tableau_vec.len() > i {
+                c.append(self.tableau_vec[i].to_circ_with(true, synthesis).circ);
             }
-            else if optimizer == "TOHPE" {
-                self.phase_polynomials[i].table = tohpe(table.clone(), self.nb_qubits);
+        }
+        c
+    }
+
+    pub fn t_par(&self, nb_ancillas: usize, synthesis: &str) -> Circuit {
+        let mut c = self.init_circuit.clone();
+        c.nb_qubits += nb_ancillas;
+        for i in 0..self.phase_polynomials.len() {
+            c.append(self.phase_polynomials[i].to_circ_layered(nb_ancillas).circ);
+            if self.tableau_vec.len() > i {
+                c.append(self.tableau_vec[i].to_circ_with(true, synthesis).circ);
             }
-            else {
-                println!("Optimizer not implemented: {}", optimizer); std::process::exit(1);
+        }
+        c
+    }
+
+    pub fn t_opt_arch(&mut self, optimizer: &dyn PhasePolynomialOptimizer, arch: &Architecture) -> Circuit {
+        if arch.nb_qubits != self.nb_qubits {
+            fail!("The architecture has {} qubits but the circuit has {}", arch.nb_qubits, self.nb_qubits);
+        }
+        let mut c = SlicedCircuit::clifford_tableau(&self.init_circuit).to_circ_arch(true, arch);
+        c.ancillas = self.init_circuit.ancillas.clone();
+        for i in 0..self.phase_polynomials.len() {
+            let table = self.phase_polynomials[i].table.clone();
+            self.phase_polynomials[i].table = optimizer.optimize(table.clone(), self.nb_qubits);
+            c.append(self.phase_polynomials[i].clifford_correction(&table, self.nb_qubits).to_circ_arch(false, arch).circ);
+            c.append(self.phase_polynomials[i].to_circ_arch(arch).circ);
+            if self.tableau_vec.len() > i {
+                c.append(self.tableau_vec[i].to_circ_arch(true, arch).circ);
+            }
+        }
+        c
+    }
+
+    // T-count optimization knowing that the qubits which are not inputs (and
+    // the qubits beyond `inputs`, such as ancillas) start in the state |0>.
+    // The parities of the qubits which are then constant are tracked through
+    // the Clifford operators and folded into each phase polynomial before it
+    // is optimized. The qubits which are not outputs are eliminated last from
+    // the final Clifford operator, so that its gates acting only on them end
+    // the circuit, where they can be removed by `Circuit::remove_discarded`.
+    pub fn t_opt_io_aware(&mut self, optimizer: &dyn PhasePolynomialOptimizer, synthesis: &str, inputs: &[bool], outputs: &[bool]) -> Circuit {
+        let mut constants: Vec<(BitVector, bool)> = (0..self.nb_qubits).filter(|&q| !inputs.get(q).copied().unwrap_or(false)).map(|q| {
+            let mut z = BitVector::new(self.nb_qubits);
+            z.xor_bit(q);
+            (z, false)
+        }).collect();
+        let mut c = self.init_circuit.clone();
+        for (gate, q) in &c.circ {
+            SlicedCircuit::track_constants(&mut constants, gate, q);
+        }
+        for i in 0..self.phase_polynomials.len() {
+            // The folded terms are only kept if they are optimized into fewer terms
+            let mut table = self.phase_polynomials[i].table.clone();
+            let mut optimized = optimizer.optimize(table.clone(), self.nb_qubits);
+            let mut folded = self.phase_polynomials[i].clone();
+            folded.fold_constants(&constants);
+            let folded_optimized = optimizer.optimize(folded.table.clone(), self.nb_qubits);
+            if folded_optimized.len() < optimized.len() {
+                table = folded.table;
+                optimized = folded_optimized;
             }
-            c.append(self.phase_polynomials[i].clifford_correction(&table, self.nb_qubits).to_circ(false).circ);
+            self.phase_polynomials[i].table = optimized;
+            c.append(self.phase_polynomials[i].clifford_correction(&table, self.nb_qubits).to_circ_with(false, synthesis).circ);
             c.append(self.phase_polynomials[i].to_circ().circ);
             if self.tableau_vec.len() > i {
-                c.append(self.tableau_vec[i].to_circ(true).circ);
+                let clifford = if i + 1 == self.tableau_vec.len() {
+                    SlicedCircuit::discarded_last(&self.tableau_vec[i], synthesis, outputs)
+                } else {
+                    self.tableau_vec[i].to_circ_with(true, synthesis)
+                };
+                for (gate, q) in &clifford.circ {
+                    SlicedCircuit::track_constants(&mut constants, gate, q);
+                }
+                c.append(clifford.circ);
             }
         }
         c
     }
+
+    // Updates the parities known to be constant, with their values, across a
+    // Clifford gate. A Hadamard gate only keeps the combinations of these
+    // parities which do not involve its qubit.
+     fn track_constants(constants: &mut Vec<(BitVector, bool)>, gate: &str, q: &[usize]) {
+        match gate {
+            "x" => {
+                for (z, value) in constants.iter_mut() {
+                    if z.get(q[0]) { *value ^= true; }
+                }
+            },
+            "cx" => {
+                for (z, _) in constants.iter_mut() {
+                    if z.get(q[1]) { z.xor_bit(q[0]); }
+                }
+            },
+            "z" | "s" | "t" => {},
+            _ => {
+                for &a in q {
+                    let pivot = match constants.iter().position(|(z, _)| z.get(a)) {
+                        Some(k) => constants.remove(k),
+                        None => continue,
+                    };
+                    for (z, value) in constants.iter_mut() {
+                        if z.get(a) {
+                            z.xor(&pivot.0);
+                            *value ^= pivot.1;
+                        }
+                    }
+                }
+            },
+        }
+    }
+
+    // Synthesis of the inverse of the tableau in which the qubits which are not
+    // outputs are eliminated last, the remaining gates acting only on them. It
+    // is only used if it has fewer gates once these are removed.
+     fn discarded_last(tab: &TableauColumnMajor, synthesis: &str, outputs: &[bool]) -> Circuit {
+        let default = tab.to_circ_with(true, synthesis);
+        let mut order: Vec<usize> = (0..tab.nb_qubits).filter(|&q| outputs.get(q).copied().unwrap_or(true)).collect();
+        if order.len() == tab.nb_qubits { return default; }
+        order.extend((0..tab.nb_qubits).filter(|&q| !outputs.get(q).copied().unwrap_or(true)));
+        let mut position = vec![0; tab.nb_qubits];
+        for (k, &q) in order.iter().enumerate() {
+            position[q] = k;
+        }
+        let mut permuted = tab.to_circ(true);
+        for (_, q) in permuted.circ.iter_mut() {
+            for a in q.iter_mut() { *a = position[*a]; }
+        }
+        let mut c = SlicedCircuit::clifford_tableau(&permuted).to_circ(true);
+        for (_, q) in c.circ.iter_mut() {
+            for a in q.iter_mut() { *a = order[*a]; }
+        }
+        if c.remove_discarded(outputs).circ.len() < default.remove_discarded(outputs).circ.len() { c } else { default }
+    }
+
+    // Tableau whose synthesis in inverse mode implements the Clifford circuit.
+     fn clifford_tableau(c: &Circuit) -> TableauColumnMajor {
+        let mut tab = TableauColumnMajor::new(c.nb_qubits);
+        for (gate, q) in &c.circ {
+            match &gate[..] {
+                "h" => { tab.prepend_h(q[0]); },
+                "x" => { tab.prepend_x(q[0]); },
+                "z" => { tab.prepend_z(q[0]); },
+                "s" => { tab.prepend_s(q[0]); tab.prepend_z(q[0]); },
+                "cx" => { tab.prepend_cx(q.to_vec()); },
+                _ => {fail!("Operator not implemented: {}", gate)},
+            }
+        }
+        tab
+    }
 }
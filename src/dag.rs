@@ -0,0 +1,232 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use crate::bit_vector::BitVector;
+use crate::circuit::Circuit;
+use crate::pauli_product::PauliProduct;
+
+// Basis in which a gate acts on one of its qubits. The gates acting on a qubit
+// in the Z basis (diagonal gates and controls) commute with each other, as do
+// the gates acting on it in the X basis (X gates and targets).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Basis {
+    Z,
+    X,
+    Other,
+}
+
+pub fn basis(gate: &str, qubits: &[usize], index: usize) -> Basis {
+    match gate {
+        "z" | "s" | "t" | "ccz" => Basis::Z,
+        "x" => Basis::X,
+        "cx" | "tof" | "ccx" => if index == qubits.len() - 1 { Basis::X } else { Basis::Z },
+        _ => Basis::Other,
+    }
+}
+
+// Pauli products generating a gate, restricted to the given qubits: a gate is
+// a product of exponentials of these Pauli products, e.g. exp(iπ/4 (I-Z1)(I-X2))
+// for a CNOT gate, which is generated by Z1, X2 and Z1 X2. Returns None for the
+// gates which are not generated by Pauli products, such as the Hadamard gate,
+// measurements and classically controlled gates.
+pub fn generators(gate: &str, qubits: &[usize], support: &[usize]) -> Option<Vec<PauliProduct>> {
+    let (z_qubits, x_qubits): (Vec<usize>, Vec<usize>) = match gate {
+        "z" | "s" | "t" | "ccz" => (qubits.to_vec(), Vec::new()),
+        "x" => (Vec::new(), qubits.to_vec()),
+        "cx" | "tof" | "ccx" => (qubits[..qubits.len() - 1].to_vec(), vec![qubits[qubits.len() - 1]]),
+        _ => return None,
+    };
+    let single = |q: usize| {
+        let mut bv = BitVector::new(support.len());
+        if let Some(i) = support.iter().position(|&s| s == q) { bv.xor_bit(i); }
+        bv
+    };
+    let mut products = Vec::new();
+    for subset in 1..(1usize << qubits.len()) {
+        let mut z = BitVector::new(support.len());
+        let mut x = BitVector::new(support.len());
+        for (i, &q) in qubits.iter().enumerate() {
+            if subset >> i & 1 == 0 { continue; }
+            if z_qubits.contains(&q) { z.xor(&single(q)); }
+            if x_qubits.contains(&q) { x.xor(&single(q)); }
+        }
+        products.push(PauliProduct::new(z, x, false));
+    }
+    Some(products)
+}
+
+// Commutation oracle for Clifford+T gates. Gates acting on distinct qubits,
+// identical gates, and gates acting in the same basis on each of their shared
+// qubits commute. Otherwise, two gates generated by Pauli products commute
+// when all their generators do.
+pub fn commute(gate1: &str, qubits1: &[usize], gate2: &str, qubits2: &[usize]) -> bool {
+    let shared: Vec<usize> = qubits1.iter().copied().filter(|q| qubits2.contains(q)).collect();
+    if shared.is_empty() || (gate1 == gate2 && qubits1 == qubits2 && !Circuit::is_barrier(gate1)) { return true; }
+    if shared.iter().all(|q| {
+        let b1 = basis(gate1, qubits1, qubits1.iter().position(|p| p == q).unwrap());
+        let b2 = basis(gate2, qubits2, qubits2.iter().position(|p| p == q).unwrap());
+        b1 == b2 && b1 != Basis::Other
+    }) { return true; }
+    match (generators(gate1, qubits1, &shared), generators(gate2, qubits2, &shared)) {
+        (Some(g1), Some(g2)) => g1.iter().all(|p1| g2.iter().all(|p2| p1.is_commuting(p2))),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub gate: String,
+    pub qubits: Vec<usize>,
+    // Previous and next nodes on the wire of each qubit of the gate.
+    pub previous: Vec<Option<usize>>,
+    pub next: Vec<Option<usize>>,
+}
+
+impl Node {
+     fn wire(&self, qubit: usize) -> usize {
+        self.qubits.iter().position(|&q| q == qubit).unwrap()
+    }
+}
+
+// Circuit as a DAG whose nodes are the gates, each qubit being a wire linking
+// the gates acting on it in order. The removed nodes are kept as None so that
+// the indices of the other nodes do not change.
+#[derive(Debug, Clone)]
+pub struct Dag {
+    pub nb_qubits: usize,
+    pub ancillas: HashMap<usize, Option<usize>>,
+    pub nodes: Vec<Option<Node>>,
+    pub first: Vec<Option<usize>>,
+    pub last: Vec<Option<usize>>,
+}
+
+impl Dag {
+    pub fn new(nb_qubits: usize) -> Self {
+        Dag {
+            nb_qubits,
+            ancillas: HashMap::new(),
+            nodes: Vec::new(),
+            first: vec![None; nb_qubits],
+            last: vec![None; nb_qubits],
+        }
+    }
+
+    pub fn from_circ(c: &Circuit) -> Dag {
+        let mut dag = Dag::new(c.nb_qubits);
+        dag.ancillas = c.ancillas.clone();
+        for (gate, qubits) in &c.circ {
+            dag.push(gate, qubits);
+        }
+        dag
+    }
+
+    // Circuit made of the gates of the DAG in a topological order, the gates
+    // being kept in the order of their indices whenever possible.
+    pub fn to_circ(&self) -> Circuit {
+        let mut c = Circuit::new(self.nb_qubits);
+        c.ancillas = self.ancillas.clone();
+        for id in self.topological_order() {
+            let node = self.node(id);
+            c.circ.push((node.gate.to_string(), node.qubits.to_vec()));
+        }
+        c
+    }
+
+    pub fn topological_order(&self) -> Vec<usize> {
+        let mut remaining: Vec<usize> = self.nodes.iter().map(|n| n.as_ref().map_or(0, |n| n.previous.iter().flatten().count())).collect();
+        let mut heap: BinaryHeap<Reverse<usize>> = self.ids().filter(|&id| remaining[id] == 0).map(Reverse).collect();
+        let mut order = Vec::new();
+        while let Some(Reverse(id)) = heap.pop() {
+            order.push(id);
+            for &next in self.node(id).next.iter().flatten() {
+                remaining[next] -= 1;
+                if remaining[next] == 0 { heap.push(Reverse(next)); }
+            }
+        }
+        order
+    }
+
+    // Indices of the nodes which have not been removed.
+    pub fn ids(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.nodes.len()).filter(|&id| self.nodes[id].is_some())
+    }
+
+    pub fn node(&self, id: usize) -> &Node {
+        self.nodes[id].as_ref().unwrap()
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.nodes.get(id).is_some_and(|n| n.is_some())
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids().next().is_none()
+    }
+
+    pub fn next(&self, id: usize, qubit: usize) -> Option<usize> {
+        let node = self.node(id);
+        node.next[node.wire(qubit)]
+    }
+
+    pub fn previous(&self, id: usize, qubit: usize) -> Option<usize> {
+        let node = self.node(id);
+        node.previous[node.wire(qubit)]
+    }
+
+    // Appends a gate at the end of the wires of its qubits, returns its index.
+    pub fn push(&mut self, gate: &str, qubits: &[usize]) -> usize {
+        let id = self.nodes.len();
+        let previous: Vec<Option<usize>> = qubits.iter().map(|&q| self.last[q]).collect();
+        for (&q, p) in qubits.iter().zip(&previous) {
+            match p {
+                Some(p) => {
+                    let node = self.nodes[*p].as_mut().unwrap();
+                    let wire = node.wire(q);
+                    node.next[wire] = Some(id);
+                },
+                None => self.first[q] = Some(id),
+            }
+            self.last[q] = Some(id);
+        }
+        self.nodes.push(Some(Node { gate: gate.to_string(), qubits: qubits.to_vec(), previous, next: vec![None; qubits.len()] }));
+        id
+    }
+
+    // Removes a node, linking its predecessor and successor on each wire.
+    pub fn remove(&mut self, id: usize) {
+        let node = self.nodes[id].take().unwrap();
+        for (i, &q) in node.qubits.iter().enumerate() {
+            let (previous, next) = (node.previous[i], node.next[i]);
+            match previous {
+                Some(p) => {
+                    let p = self.nodes[p].as_mut().unwrap();
+                    let wire = p.wire(q);
+                    p.next[wire] = next;
+                },
+                None => self.first[q] = next,
+            }
+            match next {
+                Some(n) => {
+                    let n = self.nodes[n].as_mut().unwrap();
+                    let wire = n.wire(q);
+                    n.previous[wire] = previous;
+                },
+                None => self.last[q] = previous,
+            }
+        }
+    }
+
+    // Replaces the gate of a node by another gate acting on the same qubits.
+    pub fn replace(&mut self, id: usize, gate: &str) {
+        self.nodes[id].as_mut().unwrap().gate = gate.to_string();
+    }
+
+    // Whether the gates of two nodes commute, according to `commute`.
+    pub fn commute(&self, id1: usize, id2: usize) -> bool {
+        let (n1, n2) = (self.node(id1), self.node(id2));
+        commute(&n1.gate, &n1.qubits, &n2.gate, &n2.qubits)
+    }
+}
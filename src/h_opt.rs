@@ -1,52 +1,72 @@
+use crate::fail;
 use crate::pauli_product::PauliProduct;
 use crate::tableau::Tableau;
 use crate::circuit::Circuit;
+use crate::architecture::Architecture;
+use crate::peephole::peephole;
 
- fn implement_pauli_z_rotation_from_pauli_product(tab: &mut Tableau, p: &PauliProduct) -> Circuit {
+ fn parity_network(nb_qubits: usize, pivot: usize, mut indices: Vec<usize>, arch: Option<&Architecture>) -> (Circuit, Circuit) {
+    let mut cnot_circ = Circuit::new(nb_qubits);
+    if let Some(arch) = arch {
+        indices.push(pivot);
+        cnot_circ.circ = arch.steiner_parity(pivot, &indices, &vec![true; nb_qubits]);
+        let mut uncompute = cnot_circ.clone();
+        uncompute.circ.reverse();
+        return (cnot_circ, uncompute);
+    }
+    for j in indices {
+        cnot_circ.circ.push(("cx".into(), vec![j, pivot]));
+    }
+    (cnot_circ.clone(), cnot_circ)
+}
+
+ fn implement_pauli_z_rotation_from_pauli_product(tab: &mut Tableau, p: &PauliProduct, arch: Option<&Architecture>) -> Circuit {
     let mut c = Circuit::new(tab.nb_qubits);
-    let mut cnot_circ = Circuit::new(tab.nb_qubits);
     let pivot = p.z.get_first_one();
     let mut indices = p.z.get_all_ones(tab.nb_qubits);
     indices.swap_remove(0);
-    for j in indices {
-        cnot_circ.circ.push(("cx".into(), vec![j, pivot]));
-    }
-    c.append(cnot_circ.clone().circ);
+    let (cnot_circ, uncompute) = parity_network(tab.nb_qubits, pivot, indices, arch);
+    c.append(cnot_circ.circ);
     c.circ.push(("t".into(), vec![pivot]));
     if p.sign {
         c.circ.push(("s".into(), vec![pivot]));
         c.circ.push(("z".into(), vec![pivot]));
     }
-    c.append(cnot_circ.circ);
+    c.append(uncompute.circ);
     c
 }
 
- fn implement_pauli_z_rotation(tab: &mut Tableau, col: usize) -> Circuit {
+ fn implement_pauli_z_rotation(tab: &mut Tableau, col: usize, arch: Option<&Architecture>) -> Circuit {
     let pivot = tab.z.iter().position(|z| z.get(col)).unwrap();
     let mut c = Circuit::new(tab.nb_qubits);
-    let mut cnot_circ = Circuit::new(tab.nb_qubits);
-    for j in 0..tab.nb_qubits {
-        if tab.z[j].get(col) && j != pivot {
-            cnot_circ.circ.push(("cx".into(), vec![j, pivot]));
-        }
-    }
-    c.append(cnot_circ.clone().circ);
+    let indices = (0..tab.nb_qubits).filter(|&j| tab.z[j].get(col) && j != pivot).collect();
+    let (cnot_circ, uncompute) = parity_network(tab.nb_qubits, pivot, indices, arch);
+    c.append(cnot_circ.circ);
     c.circ.push(("t".into(), vec![pivot]));
     if tab.signs.get(col) {
         c.circ.push(("s".into(), vec![pivot]));
         c.circ.push(("z".into(), vec![pivot]));
     }
-    c.append(cnot_circ.circ);
+    c.append(uncompute.circ);
     c
 }
 
- fn implement_pauli_rotation(tab: &mut Tableau, col: usize) -> Circuit {
+ fn implement_pauli_rotation(tab: &mut Tableau, col: usize, arch: Option<&Architecture>) -> Circuit {
     let mut c = Circuit::new(tab.nb_qubits);
     if let Some(pivot) = tab.x.iter().position(|x| x.get(col)) {
-        for j in 0..tab.nb_qubits {
-            if tab.x[j].get(col) && j != pivot {
-                tab.append_cx(vec![pivot, j]);
-                c.circ.push(("cx".into(), vec![pivot, j]));
+        if let Some(arch) = arch {
+            let ones: Vec<usize> = (0..tab.nb_qubits).filter(|&j| tab.x[j].get(col)).collect();
+            for (a, b) in arch.steiner_reduce(pivot, &ones, &vec![true; tab.nb_qubits]) {
+                tab.append_cx(vec![a, b]);
+                c.circ.push(("cx".into(), vec![a, b]));
+            }
+        }
+        else {
+            for j in 0..tab.nb_qubits {
+                if tab.x[j].get(col) && j != pivot {
+                    tab.append_cx(vec![pivot, j]);
+                    c.circ.push(("cx".into(), vec![pivot, j]));
+                }
             }
         }
         if tab.z[pivot].get(col) {
@@ -56,35 +76,35 @@ use crate::circuit::Circuit;
         tab.append_h(pivot);
         c.circ.push(("h".into(), vec![pivot]));
     }
-    c.append(implement_pauli_z_rotation(tab, col).circ);
+    c.append(implement_pauli_z_rotation(tab, col, arch).circ);
     c
 }
 
 
- fn implement_tof(tab: &mut Tableau, cols: Vec::<usize>, h_gate: bool) -> Circuit {
+ fn implement_tof(tab: &mut Tableau, cols: Vec::<usize>, h_gate: bool, arch: Option<&Architecture>) -> Circuit {
     let mut c = Circuit::new(tab.nb_qubits);
-    c.append(implement_pauli_rotation(tab, cols[0]).circ);
-    c.append(implement_pauli_rotation(tab, cols[1]).circ);
-    c.append(implement_pauli_rotation(tab, cols[2] + tab.nb_qubits * (h_gate as usize)).circ);
+    c.append(implement_pauli_rotation(tab, cols[0], arch).circ);
+    c.append(implement_pauli_rotation(tab, cols[1], arch).circ);
+    c.append(implement_pauli_rotation(tab, cols[2] + tab.nb_qubits * (h_gate as usize), arch).circ);
     let mut p0 = tab.extract_pauli_product(cols[0]);
     let mut p1 = tab.extract_pauli_product(cols[1]);
     let p2 = tab.extract_pauli_product(cols[2] + tab.nb_qubits * (h_gate as usize));
     p0.z.xor(&p1.z);
     p0.sign ^= p1.sign ^ true;
-    c.append(implement_pauli_z_rotation_from_pauli_product(tab, &p0).circ);
+    c.append(implement_pauli_z_rotation_from_pauli_product(tab, &p0, arch).circ);
     p0.z.xor(&p2.z);
     p0.sign ^= p2.sign ^ true;
-    c.append(implement_pauli_z_rotation_from_pauli_product(tab, &p0).circ);
+    c.append(implement_pauli_z_rotation_from_pauli_product(tab, &p0, arch).circ);
     p0.z.xor(&p1.z);
     p0.sign ^= p1.sign ^ true;
-    c.append(implement_pauli_z_rotation_from_pauli_product(tab, &p0).circ);
+    c.append(implement_pauli_z_rotation_from_pauli_product(tab, &p0, arch).circ);
     p1.z.xor(&p2.z);
     p1.sign ^= p2.sign ^ true;
-    c.append(implement_pauli_z_rotation_from_pauli_product(tab, &p1).circ);
+    c.append(implement_pauli_z_rotation_from_pauli_product(tab, &p1, arch).circ);
     c
 }
 
- fn h_opt_reverse(c_in: &Circuit) -> Tableau{
+ fn h_opt_reverse(c_in: &Circuit, arch: Option<&Architecture>) -> Tableau{
     let mut tab = Tableau::new(c_in.nb_qubits);
     for (gate, q) in &c_in.circ {
         match &gate[..] {
@@ -94,7 +114,7 @@ use crate::circuit::Circuit;
             "s" => { tab.prepend_s(q[0]); tab.prepend_z(q[0]); },
             "cx" => { tab.prepend_cx(q.to_vec()); },
             "t" | "ccz" | "tof" => continue,
-            _ => {println!("Operator not implemented: {}", gate); std::process::exit(1)},
+            _ => {fail!("Operator not implemented: {}", gate)},
         }
     }
     for (gate, q) in c_in.circ.iter().rev() {
@@ -104,18 +124,40 @@ use crate::circuit::Circuit;
             "z" => { tab.prepend_z(q[0]); },
             "s" => { tab.prepend_s(q[0]); },
             "cx" => { tab.prepend_cx(q.to_vec()); },
-            "t" => { implement_pauli_rotation(&mut tab, q[0]); },
-            "tof" => { implement_tof(&mut tab, q.to_vec(), true); },
-            "ccz" => { implement_tof(&mut tab, q.to_vec(), false); },
-            _ => {println!("Operator not implemented: {}", gate); std::process::exit(1)},
+            "t" => { implement_pauli_rotation(&mut tab, q[0], arch); },
+            "tof" => { implement_tof(&mut tab, q.to_vec(), true, arch); },
+            "ccz" => { implement_tof(&mut tab, q.to_vec(), false, arch); },
+            _ => {fail!("Operator not implemented: {}", gate)},
         }
     }
     tab
 }
 
 pub fn internal_h_opt(c_in: &Circuit) -> Circuit {
-    let mut tab = h_opt_reverse(c_in);
-    let mut c = tab.to_circ(false);
+    h_opt(c_in, None, "Elimination")
+}
+
+pub fn internal_h_opt_with(c_in: &Circuit, synthesis: &str) -> Circuit {
+    h_opt(c_in, None, synthesis)
+}
+
+pub fn internal_h_opt_arch(c_in: &Circuit, arch: &Architecture) -> Circuit {
+    h_opt(c_in, Some(arch), "Elimination")
+}
+
+ fn to_circ(tab: &Tableau, inverse: bool, arch: Option<&Architecture>, synthesis: &str) -> Circuit {
+    match arch {
+        Some(arch) => tab.to_circ_arch(inverse, arch),
+        None => tab.to_circ_with(inverse, synthesis),
+    }
+}
+
+ fn h_opt(c_in: &Circuit, arch: Option<&Architecture>, synthesis: &str) -> Circuit {
+    if c_in.has_barriers() {
+        return c_in.map_unitary_parts(|c| h_opt(&c, arch, synthesis));
+    }
+    let mut tab = h_opt_reverse(c_in, arch);
+    let mut c = to_circ(&tab, false, arch, synthesis);
     for (gate, q) in &c_in.circ {
         match &gate[..] {
             "h" => { tab.prepend_h(q[0]); },
@@ -123,12 +165,12 @@ pub fn internal_h_opt(c_in: &Circuit) -> Circuit {
             "z" => { tab.prepend_z(q[0]); },
             "s" => { tab.prepend_s(q[0]); tab.prepend_z(q[0]); },
             "cx" => { tab.prepend_cx(q.to_vec()); },
-            "t" => { c.append(implement_pauli_rotation(&mut tab, q[0]).circ); },
-            "tof" => { c.append(implement_tof(&mut tab, q.to_vec(), true).circ); },
-            "ccz" => { c.append(implement_tof(&mut tab, q.to_vec(), false).circ); },
-            _ => {println!("Operator not implemented: {}", gate); std::process::exit(1)},
+            "t" => { c.append(implement_pauli_rotation(&mut tab, q[0], arch).circ); },
+            "tof" => { c.append(implement_tof(&mut tab, q.to_vec(), true, arch).circ); },
+            "ccz" => { c.append(implement_tof(&mut tab, q.to_vec(), false, arch).circ); },
+            _ => {fail!("Operator not implemented: {}", gate)},
         }
     }
-    c.append(tab.to_circ(true).circ);
-    c
+    c.append(to_circ(&tab, true, arch, synthesis).circ);
+    peephole(&c)
 }
@@ -1,4 +1,8 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 pub mod circuit;
+pub mod dag;
 pub mod pauli_product;
 pub mod phase_polynomial;
 pub mod tableau;
@@ -6,3 +10,83 @@ pub mod bit_vector;
 pub mod h_opt;
 pub mod t_merge;
 pub mod t_opt;
+pub mod architecture;
+pub mod routing;
+pub mod schedule;
+pub mod peephole;
+pub mod resynthesis;
+pub mod pass;
+pub mod server;
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static STDERR: AtomicBool = AtomicBool::new(false);
+
+// Silences the progress messages of the passes.
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+// Prints the messages on the standard error, so that the standard output only
+// contains the optimized circuit.
+pub fn set_stderr(stderr: bool) {
+    STDERR.store(stderr, Ordering::Relaxed);
+}
+
+pub fn is_stderr() -> bool {
+    STDERR.load(Ordering::Relaxed)
+}
+
+#[macro_export]
+macro_rules! message {
+    ($($arg:tt)*) => {
+        if $crate::is_stderr() { eprintln!($($arg)*); } else { println!($($arg)*); }
+    };
+}
+
+#[macro_export]
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if !$crate::is_quiet() { $crate::message!($($arg)*); }
+    };
+}
+
+static RECOVERABLE: AtomicBool = AtomicBool::new(false);
+
+// Makes the errors recoverable: instead of exiting, `fail` panics with the
+// error message, which can then be caught with std::panic::catch_unwind.
+pub fn set_recoverable(recoverable: bool) {
+    RECOVERABLE.store(recoverable, Ordering::Relaxed);
+}
+
+thread_local! {
+    static FAILING: Cell<bool> = const { Cell::new(false) };
+}
+
+// Installs a panic hook which does not report the recoverable errors of
+// `fail`, the other panics being reported by the previous hook.
+pub fn silence_failures() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if !FAILING.with(|failing| failing.replace(false)) { previous(info); }
+    }));
+}
+
+pub fn fail(error: String) -> ! {
+    if RECOVERABLE.load(Ordering::Relaxed) {
+        FAILING.with(|failing| failing.set(true));
+        std::panic::panic_any(error);
+    }
+    message!("{}", error);
+    std::process::exit(1)
+}
+
+#[macro_export]
+macro_rules! fail {
+    ($($arg:tt)*) => {
+        $crate::fail(format!($($arg)*))
+    };
+}
@@ -1,61 +1,519 @@
-mod circuit;
-mod pauli_product;
-mod phase_polynomial;
-mod tableau;
-mod bit_vector;
-mod h_opt;
-mod t_merge;
-mod t_opt;
-use crate::h_opt::internal_h_opt;
-use crate::circuit::Circuit;
-use crate::t_merge::*;
+use quantum_circuit_optimization::h_opt::{internal_h_opt_with, internal_h_opt_arch};
+use quantum_circuit_optimization::architecture::Architecture;
+use quantum_circuit_optimization::routing::route;
+use quantum_circuit_optimization::schedule::schedule;
+use std::collections::HashMap;
+use quantum_circuit_optimization::circuit::{Circuit, CircuitStats};
+use serde_json::{json, Value};
+use quantum_circuit_optimization::t_merge::*;
+use quantum_circuit_optimization::t_opt::optimizer_from_name;
+use quantum_circuit_optimization::pass::{Cost, Pipeline, Portfolio};
+use quantum_circuit_optimization::{fail, message, progress, set_quiet, set_recoverable, set_stderr, silence_failures};
+use quantum_circuit_optimization::server::{serve_socket, Server};
 use std::path::Path;
+use std::time::Instant;
+use std::fs::File;
+use std::io::Write;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 
 fn help() {
-    println!("cargo run -r [OPTIONS] file.qc\n\nOptional arguments (case-insensitive, no order):");
-    println!("'BBMerge': runs the BBMerge algorithm");
-    println!("'FastTMerge': runs the FastTMerge algorithm");
-    println!("'InternalHOpt': runs the InternalHOpt algorithm");
-    println!("'TOHPE': runs the TOHPE algorithm");
-    println!("'FastTODD': runs the FastTODD algorithm");
+    message!("cargo run -r -- [OPTIONS] --input file.qc\n\nOptions:");
+    message!("--input FILE: reads the circuit from FILE in the .qc or OpenQASM 2.0 format, detected from its content, or from the standard input if FILE is '-'");
+    message!("             if FILE is a directory, optimizes every .qc file in it and writes a table of statistics");
+    message!("--output FILE: writes the optimized circuit to FILE (or to the directory FILE in batch mode) instead of circuits/outputs/, or to the standard output if FILE is '-'");
+    message!("--jobs N: optimizes N circuits in parallel in batch mode");
+    message!("--table FILE: writes the table of the batch mode to FILE, in the JSON format if FILE ends with .json and in the CSV format otherwise");
+    message!("--format FORMAT: writes the optimized circuit in the format qc or qasm (OpenQASM 2.0), by default the format of the output file name or of the input");
+    message!("--passes SPEC: runs the pipeline of passes SPEC (or read from the file SPEC) instead of the algorithms below, with which it cannot be combined, e.g. 'fasttmerge,internalhopt,gadgetize(budget=8),loop(fasttodd,max=5,cost=t>h>cnot)'");
+    message!("             given several times, runs the pipelines in parallel and keeps the best circuit according to the cost model (T-count, then ancillas, then CNOT count)");
+    message!("--cost C: compares the results of several pipelines with the cost C instead, e.g. 't>2*h+cnot'");
+    message!("--arch FILE: restricts CNOT gates to the coupling graph given in FILE");
+    message!("--budget N: gadgetizes at most N internal Hadamard gates, the others are kept as slice boundaries");
+    message!("--server: answers JSON-RPC optimization requests read line by line on the standard input, or on the Unix socket given with --socket PATH");
+    message!("--stats: prints the statistics of the input circuit and of the optimized circuit, in the JSON format with --stats=json");
+    message!("--quiet: prints nothing but errors, the comparison of several pipelines and, with --stats, the statistics");
+    message!("--bbmerge: runs the BBMerge algorithm");
+    message!("--fasttmerge: runs the FastTMerge algorithm");
+    message!("--internalhopt: runs the InternalHOpt algorithm");
+    message!("--tohpe: runs the TOHPE algorithm");
+    message!("--fasttodd: runs the FastTODD algorithm");
+    message!("--todd: runs the TODD algorithm");
+    message!("--exact: runs the exact T-count minimization on phase polynomials of rank at most 6, and fails on larger ranks");
+    message!("--greedyclifford: synthesizes Clifford circuits with a greedy decoupling algorithm");
+    message!("--canonicalclifford: synthesizes Clifford circuits in the canonical form F1 H F2 with Hadamard-free F1 and F2");
+    message!("--bestclifford: synthesizes Clifford circuits with the algorithm yielding the fewest CNOT gates, then Hadamard gates");
+    message!("--minimalhclifford: synthesizes Clifford circuits with the minimal number of Hadamard gates");
+    message!("--recycle: reuses the ancilla qubits of the gadgetization once they are no longer needed");
+    message!("--feedforward: measures the ancillas of the gadgetization with classically controlled corrections instead of postselecting them");
+    message!("--ioaware: assumes that the qubits missing from the .i line are initialized in |0> and that those missing from the .o line are discarded");
+    message!("--measuredoutputs: assumes that the outputs are measured in the computational basis, so that the circuit is only preserved up to a final diagonal operator");
+    message!("--route: routes the optimized circuit onto the coupling graph with SWAP gates instead of restricting CNOT gates");
+    message!("--schedule: reorders the commuting gates of the optimized circuit to reduce its depth");
+    message!("--moments: writes the optimized circuit as time steps, each one preceded by a comment");
+    message!("\nFor compatibility, the options can also be given without the leading dashes (case-insensitive, no order),");
+    message!("the input as file.qc, file.qasm or '-', the coupling graph as file.arch, 'passes=SPEC', 'cost=C', 'budget=N', and 'QASM' for --format qasm:");
+    message!("cargo run -r [OPTIONS] file.qc [file.arch]");
     std::process::exit(1);
 }
 
+const KEYWORDS: [&str; 19] = ["bbmerge", "fasttmerge", "internalhopt", "tohpe", "fasttodd", "todd", "exact",
+    "greedyclifford", "canonicalclifford", "bestclifford", "minimalhclifford",
+    "recycle", "feedforward", "qasm", "ioaware", "measuredoutputs", "route", "schedule", "moments"];
+
+#[derive(Default)]
+struct Options {
+    input: Option<String>,
+    output: Option<String>,
+    format: Option<String>,
+    arch: Option<String>,
+    passes: Vec<String>,
+    cost: Option<String>,
+    budget: Option<usize>,
+    jobs: Option<usize>,
+    table: Option<String>,
+    socket: Option<String>,
+    stats: bool,
+    stats_json: bool,
+    quiet: bool,
+    server: bool,
+    keywords: Vec<String>,
+}
+
+impl Options {
+    fn has(&self, keyword: &str) -> bool {
+        self.keywords.iter().any(|k| k == keyword)
+    }
+
+    fn set(&mut self, option: &str, value: String) {
+        let duplicate = match option {
+            "input" => self.input.replace(value).is_some(),
+            "output" => self.output.replace(value).is_some(),
+            "format" => match &value.to_lowercase()[..] {
+                "qc" | "qasm" => self.format.replace(value.to_lowercase()).is_some(),
+                _ => { message!("Format not implemented: {}", value); help(); false },
+            },
+            "arch" => self.arch.replace(value).is_some(),
+            "passes" => { self.passes.push(value); false },
+            "cost" => self.cost.replace(value).is_some(),
+            "table" => self.table.replace(value).is_some(),
+            "socket" => self.socket.replace(value).is_some(),
+            "jobs" => match value.parse() {
+                Ok(jobs) => self.jobs.replace(jobs).is_some(),
+                Err(_) => { message!("Invalid number of jobs: {}", value); help(); false },
+            },
+            _ => match value.parse() {
+                Ok(budget) => self.budget.replace(budget).is_some(),
+                Err(_) => { message!("Invalid ancilla budget: {}", value); help(); false },
+            },
+        };
+        if duplicate {
+            message!("The option --{} is given several times", option);
+            help();
+        }
+    }
+}
+
+ fn parse_args(args: &[String]) -> Options {
+    let mut options = Options::default();
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if ["--help", "-h", "help"].contains(&&arg.to_lowercase()[..]) { help(); }
+        if let Some(option) = arg.strip_prefix("--") {
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name.to_lowercase(), Some(value.to_string())),
+                None => (option.to_lowercase(), None),
+            };
+            match &name[..] {
+                "input" | "output" | "format" | "arch" | "passes" | "cost" | "budget" | "jobs" | "table" | "socket" => {
+                    let value = match value.or_else(|| args.next().cloned()) {
+                        Some(value) => value,
+                        None => { message!("Missing value for the option --{}", name); help(); String::new() },
+                    };
+                    options.set(&name, value);
+                },
+                "stats" => match value.as_deref() {
+                    None | Some("text") => options.stats = true,
+                    Some("json") => { options.stats = true; options.stats_json = true; },
+                    Some(value) => { message!("Statistics format not implemented: {}", value); help(); },
+                },
+                "quiet" if value.is_none() => options.quiet = true,
+                "server" if value.is_none() => options.server = true,
+                _ if value.is_none() && KEYWORDS.contains(&&name[..]) => options.keywords.push(name),
+                _ => { message!("Unknown option: {}", arg); help(); },
+            }
+            continue;
+        }
+        let lower = arg.to_lowercase();
+        match lower.split_once('=') {
+            Some((name, _)) if ["passes", "cost", "budget"].contains(&name) => options.set(name, arg[name.len() + 1..].to_string()),
+            Some(_) => { message!("Unknown option: {}", arg); help(); },
+            None if KEYWORDS.contains(&&lower[..]) => options.keywords.push(lower),
+            None if lower.ends_with(".arch") => options.set("arch", arg.to_string()),
+            None if lower.ends_with(".qc") || lower.ends_with(".qasm") || lower == "-" => options.set("input", arg.to_string()),
+            None => { message!("Unknown option: {}", arg); help(); },
+        }
+    }
+    if options.has("qasm") && options.format.is_none() { options.format = Some("qasm".to_string()); }
+    options
+}
+
+ fn print_statistics(title: &str, stats: &CircuitStats) {
+    message!("\n{}:\nH-count: {}\nInternal H-count: {}\nT-count: {}", title, stats.h_count, stats.internal_h_count, stats.t_count);
+    message!("CNOT count: {}\nDepth: {}\nAncillas: {}", stats.cnot_count, stats.depth, stats.ancillas);
+    let histogram: Vec<String> = stats.histogram.iter().map(|(gate, count)| format!("{}: {}", gate, count)).collect();
+    message!("T-depth: {}\nTwo-qubit depth: {}\nHadamard-free slices: {}", stats.t_depth, stats.two_qubit_depth, stats.hadamard_free_slices);
+    message!("Qubits: {}\nGates: {} ({})", stats.qubits, stats.gates, histogram.join(", "));
+}
+
+fn pad_to_architecture(c: &mut Circuit, arch: &Architecture) {
+    if c.nb_qubits > arch.nb_qubits {
+        fail!("The circuit uses {} qubits but the architecture only has {}", c.nb_qubits, arch.nb_qubits);
+    }
+    c.nb_qubits = arch.nb_qubits;
+}
+
+// Statistics of a processed circuit, written in the table of the batch mode,
+// or the error which stopped its processing.
+struct Report {
+    file: String,
+    stats: Result<(CircuitStats, CircuitStats), String>,
+    timings: Vec<(String, f64)>,
+}
+
+ fn timed<T>(timings: &mut Vec<(String, f64)>, name: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    timings.push((name.to_string(), start.elapsed().as_secs_f64()));
+    result
+}
+
+ fn output_path(directory: &str, filename: &str, format: &str) -> String {
+    Path::new(directory).join(filename).with_extension(format).to_str().unwrap().to_string()
+}
+
+// Writes the table of the batch mode, in the JSON format if the file name ends
+// with .json and in the CSV format otherwise. Without file name, the CSV table
+// is printed.
+ fn write_table(filename: Option<&str>, reports: &[Report]) {
+    let mut table = String::new();
+    if filename.is_some_and(|f| f.to_lowercase().ends_with(".json")) {
+        let rows: Vec<Value> = reports.iter().map(|r| match &r.stats {
+            Ok((initial, optimized)) => json!({
+                "file": r.file,
+                "initial": initial.to_json(),
+                "optimized": optimized.to_json(),
+                "timings": r.timings.iter().map(|(name, time)| json!({"pass": name, "seconds": time})).collect::<Vec<_>>(),
+            }),
+            Err(error) => json!({"file": r.file, "error": error}),
+        }).collect();
+        table = serde_json::to_string_pretty(&rows).unwrap() + "\n";
+    }
+    else {
+        table.push_str("file,qubits,initial T-count,T-count,initial H-count,H-count,initial internal H-count,internal H-count,initial CNOT count,CNOT count,ancillas,error");
+        let passes = reports.iter().find(|r| r.stats.is_ok()).map_or(&[][..], |r| &r.timings[..]);
+        for (name, _) in passes { table.push_str(&format!(",{} time (s)", name)); }
+        table.push('\n');
+        for r in reports {
+            match &r.stats {
+                Ok((initial, optimized)) => {
+                    table.push_str(&format!("{},{},{},{},{},{},{},{},{},{},{},", r.file, initial.qubits, initial.t_count, optimized.t_count,
+                        initial.h_count, optimized.h_count, initial.internal_h_count, optimized.internal_h_count,
+                        initial.cnot_count, optimized.cnot_count, optimized.ancillas));
+                    for (_, time) in &r.timings { table.push_str(&format!(",{:.6}", time)); }
+                },
+                // The error is quoted, since it may contain commas
+                Err(error) => {
+                    table.push_str(&format!("{},,,,,,,,,,,\"{}\"", r.file, error.replace('"', "\"\"")));
+                    for _ in passes { table.push(','); }
+                },
+            }
+            table.push('\n');
+        }
+    }
+    match filename {
+        Some(filename) => if std::fs::write(filename, table).is_err() {
+            fail!("Cannot write the table: {}", filename);
+        },
+        None => print!("{}", table),
+    }
+}
+
 fn main() {
     let args: Vec<_> = std::env::args().collect();
-    if args.iter().any(|s| s.to_lowercase().ends_with("help")) { help(); }
-    let file_index = args.iter().position(|s| s.ends_with(".qc"));
-    if file_index == None {
-        println!("No .qc file provided");
+    // When a file name is '-', the standard output may carry the optimized
+    // circuit, so the messages, including the errors of the options, are
+    // printed on the standard error.
+    if args.iter().skip(1).any(|arg| arg == "-" || arg.ends_with("=-")) { set_stderr(true); }
+    let options = parse_args(&args);
+    set_quiet(options.quiet);
+    let mut pipelines: Vec<(String, Pipeline)> = options.passes.iter().map(|spec| {
+        (spec.to_string(), if Path::new(spec).is_file() { Pipeline::from_file(spec) } else { Pipeline::parse(spec) })
+    }).collect();
+    let cost = options.cost.as_ref().map(|cost| Cost::parse(cost));
+    if cost.is_some() && pipelines.len() < 2 {
+        message!("A cost requires several pipelines of passes");
+        help();
+    }
+    let pipeline = match pipelines.len() {
+        0 => None,
+        1 => Some(pipelines.pop().unwrap().1),
+        _ => Some(Pipeline { passes: vec![Box::new(Portfolio { pipelines, cost: cost.unwrap_or(Cost::portfolio()) })] }),
+    };
+    if options.server {
+        set_stderr(true);
+        set_recoverable(true);
+        silence_failures();
+        let result = match &options.socket {
+            Some(socket) => serve_socket(socket),
+            None => Server::new().serve(std::io::stdin().lock(), std::io::stdout().lock()),
+        };
+        if let Err(e) = result { message!("Server error: {}", e); std::process::exit(1); }
+        return;
+    }
+    if options.socket.is_some() {
+        message!("The option --socket requires the option --server");
+        help();
+    }
+    let input = match &options.input {
+        Some(input) => input,
+        None => { message!("No input file provided"); help(); return },
+    };
+    let batch = Path::new(input).is_dir();
+    if batch && options.output.as_deref() == Some("-") {
+        message!("The batch mode cannot write the optimized circuits to the standard output");
+        help();
+    }
+    if !batch && (options.jobs.is_some() || options.table.is_some()) {
+        message!("The options --jobs and --table require an input directory");
         help();
     }
 
-    let do_bb_merge = args.iter().any(|s| s.to_lowercase().ends_with("bbmerge"));
-    let mut do_fast_t_merge = args.iter().any(|s| s.to_lowercase().ends_with("fasttmerge"));
-    let mut do_internal_h_opt = args.iter().any(|s| s.to_lowercase().ends_with("internalhopt"));
-    let do_tohpe = args.iter().any(|s| s.to_lowercase().ends_with("tohpe"));
-    let mut do_fast_todd = args.iter().any(|s| s.to_lowercase().ends_with("fasttodd"));
+    let do_bb_merge = options.has("bbmerge");
+    let mut do_fast_t_merge = options.has("fasttmerge");
+    let mut do_internal_h_opt = options.has("internalhopt");
+    let do_tohpe = options.has("tohpe");
+    let mut do_fast_todd = options.has("fasttodd");
+    let do_todd = options.has("todd");
+    let do_exact = options.has("exact");
+    let do_route = options.has("route");
+    let do_recycle = options.has("recycle");
+    let do_feed_forward = options.has("feedforward");
+    let do_io_aware = options.has("ioaware");
+    let do_measured_outputs = options.has("measuredoutputs");
+    let do_schedule = options.has("schedule");
+    let budget = options.budget;
+    let mut synthesis = "Elimination";
+    if options.has("greedyclifford") { synthesis = "Greedy"; }
+    if options.has("canonicalclifford") { synthesis = "Canonical"; }
+    if options.has("bestclifford") { synthesis = "Best"; }
+    if options.has("minimalhclifford") { synthesis = "MinimalH"; }
 
-    if !(do_bb_merge || do_fast_t_merge || do_internal_h_opt || do_tohpe || do_fast_todd) {
+    if !(do_bb_merge || do_fast_t_merge || do_internal_h_opt || do_tohpe || do_fast_todd || do_todd || do_exact) {
         do_fast_t_merge = true;
         do_internal_h_opt = true;
         do_fast_todd = true;
     }
+    let optimizers: Vec<_> = [("TOHPE", do_tohpe), ("TODD", do_todd), ("FastTODD", do_fast_todd), ("Exact", do_exact)]
+        .iter().filter(|(_, enabled)| *enabled).map(|(name, _)| optimizer_from_name(name)).collect();
+
+    let mut arch = options.arch.as_ref().map(|s| Architecture::from_file(s));
+    if do_route && arch.is_none() {
+        message!("Routing requires a .arch file");
+        help();
+    }
+    let route_arch = if do_route { arch.take() } else { None };
+    if arch.is_some() && do_recycle {
+        message!("Ancilla recycling renumbers the qubits, it requires the Route option with a .arch file");
+        help();
+    }
+    if do_feed_forward && (arch.is_some() || route_arch.is_some() || budget.is_some() || do_recycle) {
+        message!("Measured gadgets cannot be combined with a coupling graph, an ancilla budget or ancilla recycling");
+        help();
+    }
+    if do_feed_forward && optimizers.is_empty() {
+        message!("Measured gadgets require a T-count optimizer");
+        help();
+    }
+    if do_measured_outputs && (arch.is_some() || do_feed_forward) {
+        message!("Measured outputs cannot be combined with a coupling graph or measured gadgets");
+        help();
+    }
+    let replaced: Vec<&str> = ["bbmerge", "fasttmerge", "internalhopt", "tohpe", "fasttodd", "todd", "exact",
+        "greedyclifford", "canonicalclifford", "bestclifford", "minimalhclifford"].into_iter().filter(|k| options.has(k)).collect();
+    if pipeline.is_some() && (!replaced.is_empty() || budget.is_some()) {
+        message!("A pipeline of passes replaces the options --{}", if replaced.is_empty() { "budget".to_string() } else { replaced.join(", --") });
+        help();
+    }
+    if pipeline.is_some() && (arch.is_some() || do_feed_forward) {
+        message!("A pipeline of passes cannot be combined with a coupling graph without routing or measured gadgets");
+        help();
+    }
+    if arch.is_some() && !do_internal_h_opt && optimizers.is_empty() {
+        message!("A coupling graph requires the InternalHOpt algorithm or a T-count optimizer");
+        help();
+    }
+
+    let process = |input: &str, output: Option<&str>, output_directory: &str| -> Report {
+        let filename = if input == "-" { "stdin" } else { Path::new(input).file_name().unwrap().to_str().unwrap() };
+        let mut timings = Vec::new();
+        let text = if input == "-" { std::io::read_to_string(std::io::stdin()) } else { std::fs::read_to_string(input) };
+        let text = match text {
+            Ok(text) => text,
+            Err(_) => { fail!("Cannot read the input: {}", input) },
+        };
+        let input_format = Circuit::detect_format(&text);
+        let (mut c, header, mut qubits_mapping) = if input_format == "qasm" { Circuit::read_qasm(&text) } else { Circuit::read_qc(text.as_bytes()) };
+        progress!("File {} processed\n", filename);
+        let (inputs, outputs) = c.io_qubits(&header, &qubits_mapping);
+        let initial = c.decompose_tof().stats_with_inputs(&inputs);
+        if options.stats && !options.stats_json { print_statistics("Input circuit", &initial); }
+        if do_io_aware {
+            let nb_gates = c.circ.len();
+            c = timed(&mut timings, "IOAware", || c.propagate_constants(&inputs).remove_discarded(&outputs));
+            progress!("Removed {} gates using the .i and .o lines", nb_gates - c.circ.len());
+        }
+        let mut feed_forward = None;
+        if let Some(pipeline) = &pipeline {
+            let (pipeline_c, pipeline_timings) = pipeline.run_timed(c);
+            c = pipeline_c;
+            timings.extend(pipeline_timings);
+        }
+        else {
+            if do_bb_merge { progress!("Running BBMerge algorithm"); c = timed(&mut timings, "BBMerge", || bb_merge(c)); }
+            if do_fast_t_merge { progress!("Running FastTMerge algorithm"); c = timed(&mut timings, "FastTMerge", || fast_t_merge(c)); }
+            if do_internal_h_opt {
+                progress!("Running InternalHOpt algorithm");
+                c = timed(&mut timings, "InternalHOpt", || match &arch {
+                    Some(arch) if optimizers.is_empty() => { pad_to_architecture(&mut c, arch); internal_h_opt_arch(&c, arch) },
+                    _ => internal_h_opt_with(&c, synthesis),
+                });
+            }
+            if !optimizers.is_empty() {
+                match budget {
+                    Some(budget) => {
+                        let internal_h_count = c.get_statistics().1;
+                        progress!("Internal Hadamard gates gadgetization with at most {} ancillas", budget);
+                        c = timed(&mut timings, "Gadgetization", || c.hadamard_gadgetization_budget(budget));
+                        progress!("Gadgetized {} out of {} internal Hadamard gates", c.ancillas.len(), internal_h_count);
+                    },
+                    None if do_feed_forward => {
+                        progress!("Internal Hadamard gates gadgetization with measured ancillas");
+                        let (gadgetized_c, corrections) = timed(&mut timings, "Gadgetization", || c.hadamard_gadgetization_measured());
+                        c = gadgetized_c;
+                        feed_forward = Some(corrections);
+                    },
+                    None => { progress!("Internal Hadamard gates gadgetization"); c = timed(&mut timings, "Gadgetization", || c.hadamard_gadgetization()); },
+                }
+            }
+            if let (Some(arch), false) = (&arch, optimizers.is_empty()) { pad_to_architecture(&mut c, arch); }
+            for optimizer in &optimizers {
+                progress!("Running {} algorithm", optimizer.name());
+                c = timed(&mut timings, &optimizer.name(), || match &arch {
+                    Some(arch) => c.t_opt_arch(optimizer.as_ref(), arch),
+                    None if do_measured_outputs => c.t_opt_measured(optimizer.as_ref(), synthesis),
+                    None if do_io_aware => c.t_opt_io_aware(optimizer.as_ref(), synthesis, &inputs, &outputs),
+                    None => c.t_opt_with(optimizer.as_ref(), synthesis),
+                });
+            }
+        }
+        if do_measured_outputs { c = c.remove_final_diagonal(); }
+        if let Some(corrections) = feed_forward {
+            c.append(corrections.circ);
+        }
+        if do_io_aware { c = c.remove_discarded(&outputs); }
+
+        if do_recycle {
+            let nb_ancillas = c.ancillas.len();
+            progress!("Recycling ancillas");
+            c = timed(&mut timings, "Recycle", || c.recycle_ancillas());
+            progress!("Ancilla qubits: {} -> {}", nb_ancillas, c.ancillas.len());
+        }
 
-    let filename = Path::new(&args[file_index.unwrap()]).file_name().unwrap().to_str().unwrap();
-    let output_filename = &("circuits/outputs/".to_string() + &filename);
-    let (mut c, header, qubits_mapping) = Circuit::from_qc(&args[file_index.unwrap()]);
-    println!("File {} processed\n", filename);
-     {
-        if do_bb_merge { println!("Running BBMerge algorithm"); c = bb_merge(c); }
-        if do_fast_t_merge { println!("Running FastTMerge algorithm"); c = fast_t_merge(c); }
-        if do_internal_h_opt { println!("Running InternalHOpt algorithm"); c = internal_h_opt(&c); }
-        if do_tohpe || do_fast_todd { println!("Internal Hadamard gates gadgetization"); c = c.hadamard_gadgetization(); }
-        if do_tohpe { println!("Running TOHPE algorithm"); c = c.t_opt("TOHPE".to_string()); }
-        if do_fast_todd { println!("Running FastTODD algorithm"); c = c.t_opt("FastTODD".to_string()); }
+        if let Some(arch) = &route_arch {
+            progress!("Routing the circuit");
+            let (routed_c, placement, added_cnots) = timed(&mut timings, "Route", || route(&c, arch));
+            let mut mapping = HashMap::new();
+            for (l, p) in placement.iter().enumerate() {
+                if let Some(name) = qubits_mapping.get(&l) { mapping.insert(*p, name.to_string()); }
+            }
+            qubits_mapping = mapping;
+            c = routed_c;
+            progress!("Added CNOT gates: {}", added_cnots);
+        }
 
-        let (h_count, internal_h_count, t_count) = c.get_statistics();
-        println!("\nOptimized circuit:\nH-count: {}\nInternal H-count: {}\nT-count: {}", h_count, internal_h_count, t_count);
-        c.to_qc(output_filename, header, qubits_mapping);
+        if do_schedule {
+            progress!("Scheduling the circuit");
+            let depth = c.depth();
+            c = timed(&mut timings, "Schedule", || schedule(&c, "depth"));
+            progress!("Depth: {} -> {}", depth, c.depth());
+        }
+
+        let optimized = c.stats();
+        if options.stats_json { message!("{}", json!({"input": initial.to_json(), "optimized": optimized.to_json()})); }
+        else if options.stats || !options.quiet { print_statistics("Optimized circuit", &optimized); }
+        let format = match (options.format.as_deref(), output) {
+            (Some(format), _) => format,
+            (None, Some(output)) if output.to_lowercase().ends_with(".qasm") => "qasm",
+            (None, Some(output)) if output.to_lowercase().ends_with(".qc") => "qc",
+            _ => input_format,
+        };
+        let output_filename = match output {
+            Some(output) => output.to_string(),
+            None => output_path(output_directory, filename, format),
+        };
+        let mut file: Box<dyn Write> = if output_filename == "-" { Box::new(std::io::stdout().lock()) } else {
+            match File::create(&output_filename) {
+                Ok(file) => Box::new(file),
+                Err(_) => { fail!("Cannot write the output: {}", output_filename) },
+            }
+        };
+        if format == "qasm" { c.write_qasm_with(&mut file, options.has("moments")); }
+        else { c.write_qc_with(&mut file, header, qubits_mapping, options.has("moments")); }
+        Report { file: filename.to_string(), stats: Ok((initial, optimized)), timings }
+    };
+
+    if !batch {
+        let output = options.output.as_deref().or(if input == "-" { Some("-") } else { None });
+        process(input, output, "circuits/outputs");
+        return;
+    }
+    let mut files: Vec<String> = match std::fs::read_dir(input) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == "qc" || e == "qasm")).map(|p| p.to_str().unwrap().to_string()).collect(),
+        Err(_) => { fail!("Cannot read the directory: {}", input) },
+    };
+    files.sort();
+    let output_directory = options.output.as_deref().unwrap_or("circuits/outputs");
+    if std::fs::create_dir_all(output_directory).is_err() {
+        fail!("Cannot create the output directory: {}", output_directory);
     }
+    let jobs = options.jobs.unwrap_or(1).clamp(1, files.len().max(1));
+    // An error only stops the processing of its file, and is reported in the
+    // table. The messages of the files processed in parallel are printed on
+    // the standard error, so that they do not mix with the printed table.
+    set_stderr(true);
+    set_recoverable(true);
+    silence_failures();
+    let mut reports: Vec<(usize, Report)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..jobs).map(|j| {
+            let (files, process) = (&files, &process);
+            scope.spawn(move || files.iter().enumerate().skip(j).step_by(jobs).map(|(i, file)| {
+                let report = catch_unwind(AssertUnwindSafe(|| process(file, None, output_directory))).unwrap_or_else(|payload| {
+                    let error = match payload.downcast::<String>() {
+                        Ok(error) => *error,
+                        Err(payload) => payload.downcast_ref::<&str>().map_or("Optimization failed".to_string(), |e| e.to_string()),
+                    };
+                    message!("File {} failed: {}", file, error);
+                    let file = Path::new(file).file_name().unwrap().to_str().unwrap().to_string();
+                    Report { file, stats: Err(error), timings: Vec::new() }
+                });
+                (i, report)
+            }).collect::<Vec<_>>())
+        }).collect();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+    });
+    set_recoverable(false);
+    reports.sort_by_key(|(i, _)| *i);
+    let reports: Vec<Report> = reports.into_iter().map(|(_, r)| r).collect();
+    write_table(options.table.as_deref(), &reports);
 }
@@ -0,0 +1,399 @@
+use crate::{fail, message, progress};
+use std::collections::HashMap;
+use std::time::Instant;
+use crate::circuit::Circuit;
+use crate::h_opt::internal_h_opt_with;
+use crate::t_merge::{bb_merge, fast_t_merge};
+use crate::t_opt::{optimizer_from_name, PhasePolynomialOptimizer};
+use crate::schedule::schedule;
+use crate::peephole::peephole;
+use crate::resynthesis::{resynthesize_cliffords, SYNTHESES};
+
+pub const DEFAULT_MAX_ITERATIONS: usize = 10;
+
+pub trait Pass: Send + Sync {
+    fn name(&self) -> String;
+    fn run(&self, c: Circuit) -> Circuit;
+}
+
+pub struct BBMerge;
+
+impl Pass for BBMerge {
+    fn name(&self) -> String { "BBMerge".to_string() }
+    fn run(&self, c: Circuit) -> Circuit { bb_merge(c) }
+}
+
+pub struct FastTMerge;
+
+impl Pass for FastTMerge {
+    fn name(&self) -> String { "FastTMerge".to_string() }
+    fn run(&self, c: Circuit) -> Circuit { fast_t_merge(c) }
+}
+
+pub struct InternalHOpt {
+    pub synthesis: String,
+}
+
+impl Pass for InternalHOpt {
+    fn name(&self) -> String { "InternalHOpt".to_string() }
+    fn run(&self, c: Circuit) -> Circuit { internal_h_opt_with(&c, &self.synthesis) }
+}
+
+pub struct Gadgetize {
+    pub budget: Option<usize>,
+}
+
+impl Pass for Gadgetize {
+    fn name(&self) -> String { "Gadgetize".to_string() }
+    fn run(&self, c: Circuit) -> Circuit {
+        match self.budget {
+            Some(budget) => c.hadamard_gadgetization_budget(budget),
+            None => c.hadamard_gadgetization(),
+        }
+    }
+}
+
+pub struct TOpt {
+    pub optimizer: Box<dyn PhasePolynomialOptimizer>,
+    pub synthesis: String,
+    pub measured: bool,
+}
+
+impl Pass for TOpt {
+    fn name(&self) -> String { self.optimizer.name() }
+    fn run(&self, c: Circuit) -> Circuit {
+        if self.measured { c.t_opt_measured(self.optimizer.as_ref(), &self.synthesis) }
+        else { c.t_opt_with(self.optimizer.as_ref(), &self.synthesis) }
+    }
+}
+
+pub struct TPar {
+    pub ancillas: usize,
+    pub synthesis: String,
+}
+
+impl Pass for TPar {
+    fn name(&self) -> String { "TPar".to_string() }
+    fn run(&self, c: Circuit) -> Circuit { c.t_par(self.ancillas, &self.synthesis) }
+}
+
+pub struct Schedule {
+    pub objective: String,
+}
+
+impl Pass for Schedule {
+    fn name(&self) -> String { "Schedule".to_string() }
+    fn run(&self, c: Circuit) -> Circuit { schedule(&c, &self.objective) }
+}
+
+pub struct Peephole;
+
+impl Pass for Peephole {
+    fn name(&self) -> String { "Peephole".to_string() }
+    fn run(&self, c: Circuit) -> Circuit { peephole(&c) }
+}
+
+pub struct Resynthesize {
+    pub syntheses: Vec<String>,
+}
+
+impl Pass for Resynthesize {
+    fn name(&self) -> String { "Resynthesize".to_string() }
+    fn run(&self, c: Circuit) -> Circuit {
+        let syntheses: Vec<&str> = self.syntheses.iter().map(|s| s.as_str()).collect();
+        resynthesize_cliffords(&c, &syntheses)
+    }
+}
+
+pub struct Recycle;
+
+impl Pass for Recycle {
+    fn name(&self) -> String { "Recycle".to_string() }
+    fn run(&self, c: Circuit) -> Circuit { c.recycle_ancillas() }
+}
+
+// Cost of a circuit, made of weighted sums of metrics compared by order of
+// priority, e.g. "t>h>cnot" or "10*t+cnot>depth".
+#[derive(Clone)]
+pub struct Cost {
+    pub levels: Vec<Vec<(usize, String)>>,
+}
+
+impl Cost {
+    pub fn parse(spec: &str) -> Cost {
+        let levels = spec.split('>').map(|level| level.split('+').map(|term| {
+            let (weight, metric) = match term.split_once('*') {
+                Some((weight, metric)) => match weight.trim().parse() {
+                    Ok(weight) => (weight, metric),
+                    Err(_) => { fail!("Invalid cost: {}", spec) },
+                },
+                None => (1, term),
+            };
+            let metric = metric.trim().to_lowercase();
+            if !["t", "h", "internalh", "cnot", "depth", "tdepth", "ancillas", "qubits", "gates"].contains(&&metric[..]) {
+                fail!("Metric not implemented: {}", metric);
+            }
+            (weight, metric)
+        }).collect()).collect();
+        Cost { levels }
+    }
+
+    pub fn evaluate(&self, c: &Circuit) -> Vec<usize> {
+        let (h_count, internal_h_count, t_count) = c.get_statistics();
+        self.levels.iter().map(|level| level.iter().map(|(weight, metric)| weight * match &metric[..] {
+            "t" => t_count,
+            "h" => h_count,
+            "internalh" => internal_h_count,
+            "cnot" => c.cnot_count(),
+            "depth" => c.depth(),
+            "tdepth" => c.stats().t_depth,
+            "ancillas" => c.ancillas.len(),
+            "qubits" => c.nb_qubits,
+            _ => c.circ.len(),
+        }).sum()).collect()
+    }
+
+    // Default cost used to compare the results of a portfolio.
+    pub fn portfolio() -> Cost {
+        Cost::parse("t>ancillas>cnot")
+    }
+}
+
+impl Default for Cost {
+    fn default() -> Self {
+        Cost::parse("t>h>cnot")
+    }
+}
+
+// Runs the inner pipeline until the cost of the circuit stops decreasing, or
+// for at most `max_iterations` iterations, and returns the best circuit found.
+pub struct Loop {
+    pub pipeline: Pipeline,
+    pub cost: Cost,
+    pub max_iterations: usize,
+}
+
+impl Pass for Loop {
+    fn name(&self) -> String { "Loop".to_string() }
+    fn run(&self, c: Circuit) -> Circuit {
+        let mut best_cost = self.cost.evaluate(&c);
+        let mut best = c;
+        for i in 0..self.max_iterations {
+            let c = self.pipeline.run(best.clone());
+            let cost = self.cost.evaluate(&c);
+            progress!("Iteration {}: cost {:?}", i + 1, cost);
+            if cost >= best_cost { break; }
+            best = c;
+            best_cost = cost;
+        }
+        best
+    }
+}
+
+// Runs several pipelines in parallel on the same circuit, prints a comparison
+// of their results, even in quiet mode, and returns the one with the lowest
+// cost.
+pub struct Portfolio {
+    pub pipelines: Vec<(String, Pipeline)>,
+    pub cost: Cost,
+}
+
+impl Pass for Portfolio {
+    fn name(&self) -> String { "Portfolio".to_string() }
+    fn run(&self, c: Circuit) -> Circuit {
+        let results: Vec<Circuit> = std::thread::scope(|scope| {
+            let handles: Vec<_> = self.pipelines.iter().map(|(_, pipeline)| {
+                let c = c.clone();
+                scope.spawn(move || pipeline.run(c))
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e))).collect()
+        });
+        let costs: Vec<Vec<usize>> = results.iter().map(|c| self.cost.evaluate(c)).collect();
+        let best = (0..results.len()).min_by_key(|&i| &costs[i]).unwrap();
+        message!("\n{:<4}{:>8}{:>8}{:>8}{:>10}{:>8}  {:<16}Pipeline", "", "T", "H", "CNOT", "Ancillas", "Depth", "Cost");
+        for (i, ((spec, _), c)) in self.pipelines.iter().zip(&results).enumerate() {
+            let (h_count, _, t_count) = c.get_statistics();
+            message!("{:<4}{:>8}{:>8}{:>8}{:>10}{:>8}  {:<16}{}", if i == best { "*" } else { "" }, t_count, h_count,
+                c.cnot_count(), c.ancillas.len(), c.depth(), format!("{:?}", costs[i]), spec);
+        }
+        results.into_iter().nth(best).unwrap()
+    }
+}
+
+pub struct Pipeline {
+    pub passes: Vec<Box<dyn Pass>>,
+}
+
+impl Pipeline {
+    // A pipeline is a comma-separated list of passes, each optionally followed
+    // by parameters in parentheses, e.g.
+    // "fasttmerge,internalhopt,gadgetize(budget=8),loop(fasttodd,internalhopt)".
+    pub fn parse(spec: &str) -> Pipeline {
+        let passes = split_top_level(spec).into_iter().map(|item| {
+            let (name, params) = match item.find('(') {
+                Some(i) if item.ends_with(')') => (&item[..i], &item[i + 1..item.len() - 1]),
+                Some(_) => { fail!("Invalid pass: {}", item) },
+                None => (&item[..], ""),
+            };
+            create_pass(name.trim(), params)
+        }).collect();
+        Pipeline { passes }
+    }
+
+    // Reads a pipeline from a file, passes being separated by commas or new
+    // lines. Lines starting with '#' are ignored.
+    pub fn from_file(filename: &str) -> Pipeline {
+        let spec = match std::fs::read_to_string(filename) {
+            Ok(spec) => spec,
+            Err(_) => { fail!("Cannot open pipeline file: {}", filename) },
+        };
+        let lines: Vec<&str> = spec.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')).collect();
+        Pipeline::parse(&lines.join(","))
+    }
+
+    pub fn run(&self, c: Circuit) -> Circuit {
+        self.run_timed(c).0
+    }
+
+    // Runs the pipeline and returns the wall time of each pass in seconds.
+    pub fn run_timed(&self, mut c: Circuit) -> (Circuit, Vec<(String, f64)>) {
+        let mut timings = Vec::new();
+        for pass in &self.passes {
+            progress!("Running {} pass", pass.name());
+            let start = Instant::now();
+            c = pass.run(c);
+            timings.push((pass.name(), start.elapsed().as_secs_f64()));
+        }
+        (c, timings)
+    }
+}
+
+impl Pass for Pipeline {
+    fn name(&self) -> String { "Pipeline".to_string() }
+    fn run(&self, c: Circuit) -> Circuit { Pipeline::run(self, c) }
+}
+
+ fn split_top_level(spec: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut item = String::new();
+    for ch in spec.chars() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => { items.push(item.trim().to_string()); item = String::new(); continue },
+            _ => {},
+        }
+        item.push(ch);
+    }
+    items.push(item.trim().to_string());
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+ fn parse_params(params: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    for param in split_top_level(params) {
+        match param.split_once('=') {
+            Some((key, value)) => map.insert(key.trim().to_lowercase(), value.trim().to_string()),
+            None => map.insert(param.to_lowercase(), "true".to_string()),
+        };
+    }
+    map
+}
+
+ fn synthesis_name(params: &HashMap<String, String>) -> String {
+    let synthesis = params.get("synthesis").map(|s| s.to_lowercase()).unwrap_or("elimination".to_string());
+    match &synthesis[..] {
+        "elimination" => "Elimination",
+        "greedy" => "Greedy",
+        "canonical" => "Canonical",
+        "best" => "Best",
+        "minimalh" => "MinimalH",
+        _ => { fail!("Clifford synthesis not implemented: {}", synthesis) },
+    }.to_string()
+}
+
+// Fails on the parameters which the pass does not accept.
+ fn check_params(name: &str, params: &HashMap<String, String>, accepted: &[&str]) {
+    let mut unknown: Vec<&String> = params.keys().filter(|p| !accepted.contains(&&p[..])).collect();
+    unknown.sort();
+    if let Some(param) = unknown.first() {
+        fail!("Parameter not implemented for the pass {}: {}", name, param);
+    }
+}
+
+// Registry of the passes available in a pipeline.
+pub fn create_pass(name: &str, params: &str) -> Box<dyn Pass> {
+    if name.eq_ignore_ascii_case("loop") {
+        let (settings, passes): (Vec<String>, Vec<String>) = split_top_level(params).into_iter()
+            .partition(|item| item.find('=').is_some_and(|i| item.find('(').is_none_or(|j| i < j)));
+        let settings = parse_params(&settings.join(","));
+        check_params("loop", &settings, &["cost", "max"]);
+        return Box::new(Loop {
+            pipeline: Pipeline::parse(&passes.join(",")),
+            cost: settings.get("cost").map_or(Cost::default(), |cost| Cost::parse(cost)),
+            max_iterations: settings.get("max").map_or(DEFAULT_MAX_ITERATIONS, |max| match max.parse() {
+                Ok(max) => max,
+                Err(_) => { fail!("Invalid number of iterations: {}", max) },
+            }),
+        });
+    }
+    let params = parse_params(params);
+    let accepted: &[&str] = match &name.to_lowercase()[..] {
+        "bbmerge" | "fasttmerge" | "peephole" | "recycle" => &[],
+        "internalhopt" | "resynthesize" => &["synthesis"],
+        "gadgetize" => &["budget"],
+        "tohpe" | "todd" | "fasttodd" | "exact" => &["synthesis", "measured"],
+        "tpar" => &["ancillas", "synthesis"],
+        "schedule" => &["objective"],
+        _ => { fail!("Pass not implemented: {}", name) },
+    };
+    check_params(name, &params, accepted);
+    let t_opt = |optimizer: &str| Box::new(TOpt {
+        optimizer: optimizer_from_name(optimizer),
+        synthesis: synthesis_name(&params),
+        measured: match params.get("measured").map(|m| m.to_lowercase()).as_deref() {
+            None | Some("false") => false,
+            Some("true") => true,
+            Some(m) => { fail!("Invalid value of the parameter measured: {}", m) },
+        },
+    });
+    match &name.to_lowercase()[..] {
+        "bbmerge" => Box::new(BBMerge),
+        "fasttmerge" => Box::new(FastTMerge),
+        "internalhopt" => Box::new(InternalHOpt { synthesis: synthesis_name(&params) }),
+        "gadgetize" => Box::new(Gadgetize {
+            budget: params.get("budget").map(|b| match b.parse() {
+                Ok(budget) => budget,
+                Err(_) => { fail!("Invalid ancilla budget: {}", b) },
+            }),
+        }),
+        "tohpe" => t_opt("TOHPE"),
+        "todd" => t_opt("TODD"),
+        "fasttodd" => t_opt("FastTODD"),
+        "exact" => t_opt("Exact"),
+        "tpar" => Box::new(TPar {
+            ancillas: params.get("ancillas").map_or(0, |a| match a.parse() {
+                Ok(ancillas) => ancillas,
+                Err(_) => { fail!("Invalid number of ancillas: {}", a) },
+            }),
+            synthesis: synthesis_name(&params),
+        }),
+        "peephole" => Box::new(Peephole),
+        "resynthesize" => Box::new(Resynthesize {
+            syntheses: match params.get("synthesis") {
+                Some(_) => vec![synthesis_name(&params)],
+                None => SYNTHESES.iter().map(|s| s.to_string()).collect(),
+            },
+        }),
+        "recycle" => Box::new(Recycle),
+        "schedule" => Box::new(Schedule {
+            objective: match params.get("objective").map_or("depth".to_string(), |o| o.to_lowercase()) {
+                o if o == "depth" || o == "tdepth" => o,
+                o => { fail!("Scheduling objective not implemented: {}", o) },
+            },
+        }),
+        _ => { fail!("Pass not implemented: {}", name) },
+    }
+}
@@ -0,0 +1,116 @@
+use crate::circuit::Circuit;
+use crate::dag::Dag;
+
+// Maximal number of gates a gate is moved across to be cancelled or merged.
+pub const WINDOW: usize = 100;
+
+// Cancels and merges Clifford gates: the X and Z gates are propagated to the end
+// of the circuit, then pairs of identical self-inverse gates which can be moved
+// next to each other by commutation are cancelled, and the diagonal gates T, S
+// and Z acting on the same qubit are merged, until the circuit stops shrinking.
+// The propagation can split a Z gate into several ones, so the circuit is kept
+// unchanged if it would grow.
+pub fn peephole(c: &Circuit) -> Circuit {
+    let mut optimized = c.clone();
+    loop {
+        let nb_gates = optimized.circ.len();
+        optimized = cancel(&propagate_paulis(&optimized));
+        if optimized.circ.len() >= nb_gates { break; }
+    }
+    if optimized.circ.len() <= c.circ.len() { optimized } else { c.clone() }
+}
+
+// Moves the X and Z gates to the end of the circuit by conjugating them by the
+// Clifford gates they cross. They are applied before the gates they cannot be
+// moved across: T gates for X gates, and non-Clifford gates, measurements,
+// resets and classically controlled gates for both.
+pub fn propagate_paulis(c: &Circuit) -> Circuit {
+    let mut x = vec![false; c.nb_qubits];
+    let mut z = vec![false; c.nb_qubits];
+    let mut propagated = Circuit::new(c.nb_qubits);
+    propagated.ancillas = c.ancillas.clone();
+    let flush = |circ: &mut Vec<(String, Vec<usize>)>, pauli: &mut Vec<bool>, gate: &str, q: usize| {
+        if pauli[q] { circ.push((gate.to_string(), vec![q])); }
+        pauli[q] = false;
+    };
+    for (gate, q) in &c.circ {
+        match &gate[..] {
+            "x" => { x[q[0]] ^= true; continue; },
+            "z" => { z[q[0]] ^= true; continue; },
+            "h" => { (x[q[0]], z[q[0]]) = (z[q[0]], x[q[0]]); },
+            "s" => { z[q[0]] ^= x[q[0]]; },
+            "cx" => {
+                x[q[1]] ^= x[q[0]];
+                z[q[0]] ^= z[q[1]];
+            },
+            "t" => { flush(&mut propagated.circ, &mut x, "x", q[0]); },
+            _ => {
+                for &p in q {
+                    flush(&mut propagated.circ, &mut x, "x", p);
+                    flush(&mut propagated.circ, &mut z, "z", p);
+                }
+            },
+        }
+        propagated.circ.push((gate.to_string(), q.to_vec()));
+    }
+    for q in 0..c.nb_qubits {
+        flush(&mut propagated.circ, &mut x, "x", q);
+        flush(&mut propagated.circ, &mut z, "z", q);
+    }
+    propagated
+}
+
+ fn phase(gate: &str) -> Option<usize> {
+    match gate {
+        "t" => Some(1),
+        "s" => Some(2),
+        "z" => Some(4),
+        _ => None,
+    }
+}
+
+// Nodes following `id` on the wire of `qubit` as long as they commute with it,
+// within the window.
+ fn commuting_successors(dag: &Dag, id: usize, qubit: usize) -> Vec<usize> {
+    let mut nodes = Vec::new();
+    let mut next = dag.next(id, qubit);
+    while let Some(n) = next {
+        if nodes.len() == WINDOW || !dag.commute(id, n) { break; }
+        nodes.push(n);
+        next = dag.next(n, qubit);
+    }
+    nodes
+}
+
+// Cancels the pairs of identical self-inverse gates and merges the diagonal
+// gates T, S and Z acting on a qubit, in a single pass over the DAG.
+pub fn cancel(c: &Circuit) -> Circuit {
+    let mut dag = Dag::from_circ(c);
+    for id in dag.topological_order() {
+        if !dag.contains(id) { continue; }
+        let node = dag.node(id).clone();
+        if phase(&node.gate).is_some() {
+            let q = node.qubits[0];
+            let mut merged = vec![id];
+            merged.extend(commuting_successors(&dag, id, q).into_iter()
+                .filter(|&n| dag.node(n).qubits.len() == 1 && phase(&dag.node(n).gate).is_some()));
+            let angle = merged.iter().map(|&n| phase(&dag.node(n).gate).unwrap()).sum::<usize>() % 8;
+            let gates: Vec<&str> = [("z", 4), ("s", 2), ("t", 1)].iter().filter(|(_, a)| angle & a != 0).map(|(g, _)| *g).collect();
+            for (i, &n) in merged.iter().enumerate() {
+                if i < gates.len() { dag.replace(n, gates[i]); }
+                else { dag.remove(n); }
+            }
+        }
+        else if ["h", "x", "cx", "ccz", "tof", "ccx"].contains(&&node.gate[..]) {
+            let matching = commuting_successors(&dag, id, node.qubits[0]).into_iter()
+                .find(|&n| dag.node(n).gate == node.gate && dag.node(n).qubits == node.qubits);
+            if let Some(m) = matching {
+                if node.qubits[1..].iter().all(|&q| commuting_successors(&dag, id, q).contains(&m)) {
+                    dag.remove(id);
+                    dag.remove(m);
+                }
+            }
+        }
+    }
+    dag.to_circ()
+}
@@ -1,6 +1,7 @@
 use crate::bit_vector::BitVector;
 use crate::circuit::Circuit;
 use crate::tableau::Tableau;
+use crate::architecture::Architecture;
 
 #[derive(Debug, Clone)]
 pub struct PhasePolynomial {
@@ -22,19 +23,44 @@ impl PhasePolynomial {
             for j in (i+1)..nb_qubits {
                 let z1 = (0..table.len()).filter(|&k| table[k].get(i) & table[k].get(j)).count();
                 let z2 = (0..self.table.len()).filter(|&k| self.table[k].get(i) & self.table[k].get(j)).count();
-                for _ in 0..((((z1 - z2) % 8 + 8) % 8) / 2) {
+                for _ in 0..(((z1 % 8 + 8 - z2 % 8) % 8) / 2) {
                     tab.append_cz(vec![i, j]);
                 }
             }
             let z1 = (0..table.len()).filter(|&k| table[k].get(i)).count();
             let z2 = (0..self.table.len()).filter(|&k| self.table[k].get(i)).count();
-            for _ in 0..((((z1 - z2) % 8 + 8) % 8) / 2) {
+            for _ in 0..(((z1 % 8 + 8 - z2 % 8) % 8) / 2) {
                 tab.append_s(i);
             }
         }
         tab
     }
 
+    // Folds the parities known to be constant, given with their values, into
+    // the terms: each parity is reduced modulo the parities equal to 0, and
+    // the terms which become constant only contribute a global phase. The
+    // result only implements the phase polynomial on the states satisfying
+    // the constraints.
+    pub fn fold_constants(&mut self, constants: &[(BitVector, bool)]) {
+        let mut basis = Vec::new();
+        let mut one: Option<BitVector> = None;
+        for (z, value) in constants {
+            let mut z = z.clone();
+            if *value {
+                match &one {
+                    Some(o) => { z.xor(o); },
+                    None => { one = Some(z); continue; },
+                }
+            }
+            let r = PhasePolynomial::reduce(&z, &basis);
+            if r.popcount() != 0 { PhasePolynomial::insert(&mut basis, r); }
+        }
+        let one = one.map(|o| PhasePolynomial::reduce(&o, &basis).get_all_ones(self.nb_qubits));
+        self.table = self.table.iter().map(|z| PhasePolynomial::reduce(z, &basis))
+            .filter(|z| z.popcount() != 0 && Some(z.get_all_ones(self.nb_qubits)) != one)
+            .collect();
+    }
+
     pub fn to_circ(&self) -> Circuit {
         let mut c = Circuit::new(self.nb_qubits);
         for z in &self.table {
@@ -51,4 +77,164 @@ impl PhasePolynomial {
         }
         c
     }
+
+    // Partitions the parity table into layers of terms which can be applied at
+    // the same time, greedily adding each term to the first layer it fits in,
+    // as in the Tpar algorithm. Without ancillas the terms of a layer are linearly
+    // independent, and each ancilla allows one more linearly dependent term.
+    pub fn layers(&self, nb_ancillas: usize) -> Vec<Vec<BitVector>> {
+        let mut layers: Vec<Vec<BitVector>> = Vec::new();
+        let mut bases: Vec<Vec<(usize, BitVector)>> = Vec::new();
+        for z in &self.table {
+            if z.popcount() == 0 { continue; }
+            let mut placed = false;
+            for (layer, basis) in layers.iter_mut().zip(bases.iter_mut()) {
+                if layer.len() == self.nb_qubits + nb_ancillas { continue; }
+                let r = PhasePolynomial::reduce(z, basis);
+                if r.popcount() == 0 && layer.len() + 1 - basis.len() > nb_ancillas { continue; }
+                if r.popcount() != 0 { PhasePolynomial::insert(basis, r); }
+                layer.push(z.clone());
+                placed = true;
+                break;
+            }
+            if !placed {
+                let mut basis = Vec::new();
+                PhasePolynomial::insert(&mut basis, z.clone());
+                layers.push(vec![z.clone()]);
+                bases.push(basis);
+            }
+        }
+        layers
+    }
+
+    // Synthesizes the phase polynomial with a T-depth equal to its number of
+    // layers: the wires are mapped to the parities of a layer by a CNOT network,
+    // the T gates of the layer are applied in parallel, and so on until the
+    // wires are mapped back to the qubits. The ancillas are the wires after the
+    // qubits, initialized and left in the state |0>.
+    pub fn to_circ_layered(&self, nb_ancillas: usize) -> Circuit {
+        let n = self.nb_qubits;
+        let identity: Vec<BitVector> = (0..n + nb_ancillas).map(|i| {
+            let mut bv = BitVector::new(n);
+            if i < n { bv.xor_bit(i); }
+            bv
+        }).collect();
+        let mut c = Circuit::new(n + nb_ancillas);
+        let mut wires = identity.clone();
+        for layer in self.layers(nb_ancillas) {
+            let (target, positions) = self.layer_wires(&layer, nb_ancillas);
+            c.append(PhasePolynomial::parity_network(&wires, &target, n));
+            for q in positions {
+                c.circ.push(("t".into(), vec![q]));
+            }
+            wires = target;
+        }
+        c.append(PhasePolynomial::parity_network(&wires, &identity, n));
+        c
+    }
+
+    // Places the terms of a layer on the wires, the linearly independent ones
+    // on the qubit of their pivot and the others on the ancillas, and completes
+    // the other wires so that the qubits can be recovered from them. Returns the
+    // parities of the wires and the wires of the terms.
+     fn layer_wires(&self, layer: &[BitVector], nb_ancillas: usize) -> (Vec<BitVector>, Vec<usize>) {
+        let n = self.nb_qubits;
+        let mut wires: Vec<Option<BitVector>> = vec![None; n + nb_ancillas];
+        let mut positions = Vec::new();
+        let mut basis = Vec::new();
+        let mut next_ancilla = n;
+        for z in layer {
+            let r = PhasePolynomial::reduce(z, &basis);
+            let q = if r.popcount() != 0 {
+                let pivot = r.get_first_one();
+                PhasePolynomial::insert(&mut basis, r);
+                pivot
+            } else {
+                next_ancilla += 1;
+                next_ancilla - 1
+            };
+            wires[q] = Some(z.clone());
+            positions.push(q);
+        }
+        for j in 0..n {
+            if basis.len() == n { break; }
+            let mut e = BitVector::new(n);
+            e.xor_bit(j);
+            let r = PhasePolynomial::reduce(&e, &basis);
+            if r.popcount() == 0 { continue; }
+            PhasePolynomial::insert(&mut basis, r);
+            let q = if wires[j].is_none() { j } else { wires.iter().position(|w| w.is_none()).unwrap() };
+            wires[q] = Some(e);
+        }
+        (wires.into_iter().map(|w| w.unwrap_or_else(|| BitVector::new(n))).collect(), positions)
+    }
+
+    // CNOT network mapping the parities `from` of the wires to the parities `to`,
+    // both sets of parities spanning the n qubits: the gates reducing `from` to
+    // the identity are followed by the inverse of those reducing `to`.
+     fn parity_network(from: &[BitVector], to: &[BitVector], n: usize) -> Vec<(String, Vec<usize>)> {
+        let mut gates = PhasePolynomial::reduction(from, n);
+        gates.extend(PhasePolynomial::reduction(to, n).into_iter().rev());
+        let mut c: Vec<(String, Vec<usize>)> = Vec::new();
+        for gate in gates {
+            if c.last() == Some(&gate) { c.pop(); }
+            else { c.push(gate); }
+        }
+        c
+    }
+
+    // CNOT gates mapping the parities of the wires to the qubits, the wires
+    // after the n first ones ending in the state |0>.
+     fn reduction(wires: &[BitVector], n: usize) -> Vec<(String, Vec<usize>)> {
+        let mut rows = wires.to_vec();
+        let mut gates = Vec::new();
+        for col in 0..n {
+            if !rows[col].get(col) {
+                let p = (col + 1..rows.len()).find(|&p| rows[p].get(col)).unwrap();
+                let row = rows[p].clone();
+                rows[col].xor(&row);
+                gates.push(("cx".to_string(), vec![p, col]));
+            }
+            let row = rows[col].clone();
+            for (r, other) in rows.iter_mut().enumerate() {
+                if r != col && other.get(col) {
+                    other.xor(&row);
+                    gates.push(("cx".to_string(), vec![col, r]));
+                }
+            }
+        }
+        gates
+    }
+
+    // Reduces a parity by a basis in reduced row echelon form.
+     fn reduce(z: &BitVector, basis: &[(usize, BitVector)]) -> BitVector {
+        let mut r = z.clone();
+        for (pivot, b) in basis {
+            if r.get(*pivot) { r.xor(b); }
+        }
+        r
+    }
+
+     fn insert(basis: &mut Vec<(usize, BitVector)>, r: BitVector) {
+        let pivot = r.get_first_one();
+        for (_, b) in basis.iter_mut() {
+            if b.get(pivot) { b.xor(&r); }
+        }
+        basis.push((pivot, r));
+    }
+
+    pub fn to_circ_arch(&self, arch: &Architecture) -> Circuit {
+        let mut c = Circuit::new(self.nb_qubits);
+        let allowed = vec![true; self.nb_qubits];
+        for z in &self.table {
+            let ones = z.get_all_ones(self.nb_qubits);
+            // An empty parity only contributes a global phase
+            if ones.is_empty() { continue; }
+            let cnot_circ = arch.steiner_parity(ones[0], &ones, &allowed);
+            c.append(cnot_circ.clone());
+            c.circ.push(("t".into(), vec![ones[0]]));
+            c.append(cnot_circ.into_iter().rev().collect());
+        }
+        c
+    }
 }
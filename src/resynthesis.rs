@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+use crate::circuit::Circuit;
+use crate::dag::Dag;
+use crate::tableau::Tableau;
+
+// Clifford syntheses tried on each block by default.
+pub const SYNTHESES: [&str; 2] = ["Best", "MinimalH"];
+
+ fn is_clifford(gate: &str) -> bool {
+    ["h", "x", "z", "s", "cx"].contains(&gate)
+}
+
+// Resynthesizes the maximal Clifford sub-circuits of the circuit. The gates are
+// taken in a topological order of the DAG of the circuit: as many Clifford
+// gates as possible are gathered into a block, then the non-Clifford gates
+// which can be applied are, and so on. Each block is converted to a tableau on
+// the qubits it acts on and synthesized with each of the given syntheses, and a
+// synthesized circuit replaces the block when it has no more CNOT and Hadamard
+// gates and fewer CNOT gates, Hadamard gates or gates in total. The non-Clifford
+// gates are left untouched.
+pub fn resynthesize_cliffords(c: &Circuit, syntheses: &[&str]) -> Circuit {
+    let dag = Dag::from_circ(c);
+    let mut remaining: Vec<usize> = dag.nodes.iter().map(|n| n.as_ref().map_or(0, |n| n.previous.iter().flatten().count())).collect();
+    let (mut clifford, mut other): (Vec<usize>, Vec<usize>) = dag.ids().filter(|&id| remaining[id] == 0)
+        .partition(|&id| is_clifford(&dag.node(id).gate));
+    let mut resynthesized = Circuit::new(c.nb_qubits);
+    resynthesized.ancillas = c.ancillas.clone();
+    let mut apply = |id: usize, clifford: &mut Vec<usize>, other: &mut Vec<usize>| {
+        for &next in dag.node(id).next.iter().flatten() {
+            remaining[next] -= 1;
+            if remaining[next] > 0 { continue; }
+            if is_clifford(&dag.node(next).gate) { clifford.push(next); }
+            else { other.push(next); }
+        }
+    };
+    while !clifford.is_empty() || !other.is_empty() {
+        let mut block = Vec::new();
+        while let Some(id) = clifford.pop() {
+            let node = dag.node(id);
+            block.push((node.gate.to_string(), node.qubits.to_vec()));
+            apply(id, &mut clifford, &mut other);
+        }
+        resynthesized.append(resynthesize_block(block, syntheses));
+        while let Some(id) = other.pop() {
+            let node = dag.node(id);
+            resynthesized.circ.push((node.gate.to_string(), node.qubits.to_vec()));
+            apply(id, &mut clifford, &mut other);
+        }
+    }
+    resynthesized
+}
+
+ fn cost(circ: &[(String, Vec<usize>)]) -> (usize, usize, usize) {
+    let count = |name: &str| circ.iter().filter(|(gate, _)| gate == name).count();
+    (count("cx"), count("h"), circ.len())
+}
+
+// Synthesizes a Clifford block from its tableau on the qubits it acts on, and
+// returns the cheapest of the block and of its syntheses.
+ fn resynthesize_block(block: Vec<(String, Vec<usize>)>, syntheses: &[&str]) -> Vec<(String, Vec<usize>)> {
+    if block.len() < 2 { return block; }
+    let mut qubits: Vec<usize> = block.iter().flat_map(|(_, q)| q.iter().copied()).collect();
+    qubits.sort();
+    qubits.dedup();
+    let local: HashMap<usize, usize> = qubits.iter().enumerate().map(|(i, &q)| (q, i)).collect();
+    let mut tab = Tableau::new(qubits.len());
+    for (gate, q) in &block {
+        match &gate[..] {
+            "h" => tab.append_h(local[&q[0]]),
+            "x" => tab.append_x(local[&q[0]]),
+            "z" => tab.append_z(local[&q[0]]),
+            "s" => tab.append_s(local[&q[0]]),
+            _ => tab.append_cx(vec![local[&q[0]], local[&q[1]]]),
+        }
+    }
+    let mut best = block;
+    for synthesis in syntheses {
+        let circ: Vec<(String, Vec<usize>)> = tab.to_circ_with(false, synthesis).circ.into_iter()
+            .map(|(gate, q)| (gate, q.iter().map(|&i| qubits[i]).collect())).collect();
+        let (cnot_count, h_count, nb_gates) = cost(&circ);
+        let (best_cnot_count, best_h_count, best_nb_gates) = cost(&best);
+        if cnot_count <= best_cnot_count && h_count <= best_h_count && (cnot_count, h_count, nb_gates) < (best_cnot_count, best_h_count, best_nb_gates) {
+            best = circ;
+        }
+    }
+    best
+}
@@ -0,0 +1,130 @@
+use crate::fail;
+use crate::architecture::Architecture;
+use crate::circuit::Circuit;
+
+const LOOKAHEAD: usize = 20;
+
+pub fn initial_placement(c: &Circuit, arch: &Architecture) -> Vec<usize> {
+    if c.nb_qubits > arch.nb_qubits {
+        fail!("The circuit uses {} qubits but the architecture only has {}", c.nb_qubits, arch.nb_qubits);
+    }
+    let dist = arch.distances();
+    let mut weights = vec![vec![0; arch.nb_qubits]; arch.nb_qubits];
+    for (gate, q) in &c.circ {
+        if gate == "cx" {
+            weights[q[0]][q[1]] += 1;
+            weights[q[1]][q[0]] += 1;
+        }
+    }
+    let mut placement = vec![usize::MAX; arch.nb_qubits];
+    let mut free = vec![true; arch.nb_qubits];
+    let center = (0..arch.nb_qubits).min_by_key(|&p| dist[p].iter().sum::<usize>()).unwrap();
+    let first = (0..arch.nb_qubits).max_by_key(|&l| (weights[l].iter().sum::<usize>(), arch.nb_qubits - l)).unwrap();
+    placement[first] = center;
+    free[center] = false;
+    for _ in 1..arch.nb_qubits {
+        let placed: Vec<usize> = (0..arch.nb_qubits).filter(|&l| placement[l] != usize::MAX).collect();
+        let logical = (0..arch.nb_qubits).filter(|&l| placement[l] == usize::MAX)
+            .max_by_key(|&l| (placed.iter().map(|&m| weights[l][m]).sum::<usize>(), weights[l].iter().sum::<usize>(), arch.nb_qubits - l)).unwrap();
+        let physical = (0..arch.nb_qubits).filter(|&p| free[p])
+            .min_by_key(|&p| (placed.iter().map(|&m| weights[logical][m] * dist[p][placement[m]]).sum::<usize>(),
+                              placed.iter().map(|&m| dist[p][placement[m]]).sum::<usize>(), p)).unwrap();
+        placement[logical] = physical;
+        free[physical] = false;
+    }
+    placement
+}
+
+ fn push_swap(c: &mut Circuit, a: usize, b: usize) {
+    c.circ.push(("cx".to_string(), vec![a, b]));
+    c.circ.push(("cx".to_string(), vec![b, a]));
+    c.circ.push(("cx".to_string(), vec![a, b]));
+}
+
+ fn lookahead_cost(layout: &[usize], next: &[(usize, usize)], dist: &[Vec<usize>]) -> usize {
+    next.iter().map(|&(a, b)| dist[layout[a]][layout[b]]).sum()
+}
+
+// Maps the circuit onto the coupling graph by inserting SWAP gates (or bridge
+// gates for CNOTs at distance 2). Returns the routed circuit acting on the
+// physical qubits, the placement of the logical qubits and the number of added
+// CNOT gates. The initial placement is restored at the end of the circuit.
+pub fn route(c_in: &Circuit, arch: &Architecture) -> (Circuit, Vec<usize>, usize) {
+    let dist = arch.distances();
+    let placement = initial_placement(c_in, arch);
+    let mut layout = placement.clone();
+    let mut c = Circuit::new(arch.nb_qubits);
+    for (anc, parent) in &c_in.ancillas {
+        c.ancillas.insert(placement[*anc], parent.map(|parent| placement[parent]));
+    }
+    let c_in = c_in.decompose_tof();
+    let pairs: Vec<(usize, usize)> = c_in.circ.iter().filter(|(gate, _)| gate == "cx").map(|(_, q)| (q[0], q[1])).collect();
+    let mut added = 0;
+    let mut t = 0;
+    for (gate, q) in &c_in.circ {
+        if gate.starts_with("if_") && q.len() > 2 {
+            fail!("Routing does not support classically controlled multi-qubit gates");
+        }
+        if gate != "cx" {
+            c.circ.push((gate.to_string(), q.iter().map(|&l| layout[l]).collect()));
+            continue
+        }
+        t += 1;
+        let next = &pairs[t..pairs.len().min(t + LOOKAHEAD)];
+        loop {
+            let (pa, pb) = (layout[q[0]], layout[q[1]]);
+            let d = dist[pa][pb];
+            if d == 1 {
+                c.circ.push(("cx".to_string(), vec![pa, pb]));
+                break;
+            }
+            let mut swaps = Vec::new();
+            for n in arch.neighbors(pa) {
+                if dist[n][pb] < d { swaps.push((pa, n)); }
+            }
+            for n in arch.neighbors(pb) {
+                if dist[pa][n] < d { swaps.push((pb, n)); }
+            }
+            let mut best = None;
+            for (a, b) in swaps {
+                let mut new_layout = layout.clone();
+                for p in new_layout.iter_mut() {
+                    if *p == a { *p = b; }
+                    else if *p == b { *p = a; }
+                }
+                let cost = lookahead_cost(&new_layout, next, &dist);
+                if best.as_ref().is_none_or(|(c, _, _)| cost < *c) {
+                    best = Some((cost, (a, b), new_layout));
+                }
+            }
+            let (cost, (a, b), new_layout) = best.unwrap();
+            if d == 2 && cost >= lookahead_cost(&layout, next, &dist) {
+                let m = (0..arch.nb_qubits).find(|&m| dist[pa][m] == 1 && dist[m][pb] == 1).unwrap();
+                c.circ.push(("cx".to_string(), vec![pa, m]));
+                c.circ.push(("cx".to_string(), vec![m, pb]));
+                c.circ.push(("cx".to_string(), vec![pa, m]));
+                c.circ.push(("cx".to_string(), vec![m, pb]));
+                added += 3;
+                break;
+            }
+            push_swap(&mut c, a, b);
+            added += 3;
+            layout = new_layout;
+        }
+    }
+    let mut allowed = vec![true; arch.nb_qubits];
+    for p in arch.elimination_order() {
+        let l = placement.iter().position(|&x| x == p).unwrap();
+        let path = arch.shortest_path(layout[l], p, &allowed).unwrap();
+        for k in 1..path.len() {
+            push_swap(&mut c, path[k - 1], path[k]);
+            added += 3;
+            for x in layout.iter_mut() {
+                if *x == path[k - 1] { *x = path[k]; }
+                else if *x == path[k] { *x = path[k - 1]; }
+            }
+        }
+        allowed[p] = false;
+    }
+    (c, placement[..c_in.nb_qubits].to_vec(), added)
+}
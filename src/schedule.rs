@@ -0,0 +1,124 @@
+use crate::circuit::Circuit;
+use crate::fail;
+use crate::dag::{basis, Basis};
+
+// Dependency DAG of the circuit, given as the predecessors of each gate: a gate
+// depends on the previous gates sharing a qubit with it unless they act in the
+// same basis on all their shared qubits. The consecutive gates acting in the
+// same basis on a qubit form a group, and each gate depends on the gates of the
+// previous group of its qubits.
+pub fn dependencies(c: &Circuit) -> Vec<Vec<usize>> {
+    let mut predecessors = vec![Vec::new(); c.circ.len()];
+    let mut groups: Vec<(Basis, Vec<usize>, Vec<usize>)> = vec![(Basis::Other, Vec::new(), Vec::new()); c.nb_qubits];
+    for (i, (gate, qubits)) in c.circ.iter().enumerate() {
+        for (index, &q) in qubits.iter().enumerate() {
+            let b = basis(gate, qubits, index);
+            let (current, group, previous) = &mut groups[q];
+            if b == Basis::Other || b != *current || group.is_empty() {
+                *previous = std::mem::take(group);
+                *current = b;
+            }
+            predecessors[i].extend(previous.iter().copied());
+            group.push(i);
+        }
+        predecessors[i].sort();
+        predecessors[i].dedup();
+    }
+    predecessors
+}
+
+// Circuit as a sequence of time steps, the gates of a time step acting on
+// distinct qubits.
+pub struct Schedule {
+    pub nb_qubits: usize,
+    pub moments: Vec<Vec<(String, Vec<usize>)>>,
+}
+
+impl Schedule {
+    // Time steps of the circuit with its gates in their order, each gate being
+    // applied as soon as its qubits are available.
+    pub fn asap(c: &Circuit) -> Schedule {
+        let mut layers = vec![0; c.nb_qubits];
+        let mut moments: Vec<Vec<(String, Vec<usize>)>> = Vec::new();
+        for (gate, qubits) in &c.circ {
+            let layer = qubits.iter().map(|&q| layers[q]).max().unwrap();
+            for &q in qubits {
+                layers[q] = layer + 1;
+            }
+            if moments.len() == layer { moments.push(Vec::new()); }
+            moments[layer].push((gate.to_string(), qubits.to_vec()));
+        }
+        Schedule { nb_qubits: c.nb_qubits, moments }
+    }
+
+    // List scheduling of the dependency DAG: at each time step, the gates whose
+    // predecessors have been applied are selected by decreasing priority as long
+    // as their qubits are available. The priority of a gate is the length of the
+    // longest path starting from it, then the number of T gates on such a path,
+    // or the other way around when the objective is "tdepth".
+    pub fn new(c: &Circuit, objective: &str) -> Schedule {
+        let t_first = match &objective.to_lowercase()[..] {
+            "depth" => false,
+            "tdepth" => true,
+            _ => { fail!("Scheduling objective not implemented: {}", objective) },
+        };
+        let predecessors = dependencies(c);
+        let mut successors = vec![Vec::new(); c.circ.len()];
+        for (i, preds) in predecessors.iter().enumerate() {
+            for &p in preds {
+                successors[p].push(i);
+            }
+        }
+        let mut priority = vec![(0, 0); c.circ.len()];
+        for i in (0..c.circ.len()).rev() {
+            let path = successors[i].iter().map(|&s| priority[s].0).max().unwrap_or(0) + 1;
+            let t_path = successors[i].iter().map(|&s| priority[s].1).max().unwrap_or(0) + (c.circ[i].0 == "t") as usize;
+            priority[i] = if t_first { (t_path, path) } else { (path, t_path) };
+        }
+        let mut remaining: Vec<usize> = predecessors.iter().map(|p| p.len()).collect();
+        let mut ready: Vec<usize> = (0..c.circ.len()).filter(|&i| remaining[i] == 0).collect();
+        let mut moments = Vec::new();
+        while !ready.is_empty() {
+            ready.sort_by_key(|&i| (std::cmp::Reverse(priority[i]), i));
+            let mut busy = vec![false; c.nb_qubits];
+            let mut moment = Vec::new();
+            let mut next = Vec::new();
+            for i in ready {
+                let qubits = &c.circ[i].1;
+                if qubits.iter().any(|&q| busy[q]) {
+                    next.push(i);
+                    continue;
+                }
+                for &q in qubits {
+                    busy[q] = true;
+                }
+                moment.push(c.circ[i].clone());
+                for &s in &successors[i] {
+                    remaining[s] -= 1;
+                    if remaining[s] == 0 { next.push(s); }
+                }
+            }
+            moments.push(moment);
+            ready = next;
+        }
+        Schedule { nb_qubits: c.nb_qubits, moments }
+    }
+
+    pub fn to_circ(&self) -> Circuit {
+        let mut c = Circuit::new(self.nb_qubits);
+        for moment in &self.moments {
+            c.append(moment.clone());
+        }
+        c
+    }
+}
+
+// Reorders the commuting gates of the circuit to reduce its depth, or its
+// T-depth when the objective is "tdepth", the circuit being kept unchanged if
+// the scheduled one has a larger depth or T-depth.
+pub fn schedule(c: &Circuit, objective: &str) -> Circuit {
+    let mut scheduled = Schedule::new(c, objective).to_circ();
+    scheduled.ancillas = c.ancillas.clone();
+    let (before, after) = (c.stats(), scheduled.stats());
+    if after.depth <= before.depth && after.t_depth <= before.t_depth { scheduled } else { c.clone() }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use serde_json::{json, Value};
+use crate::circuit::Circuit;
+use crate::pass::Pipeline;
+
+pub const DEFAULT_PIPELINE: &str = "fasttmerge,internalhopt,gadgetize,fasttodd";
+
+// Optimization server answering JSON-RPC 2.0 requests, one per line.
+// The method "optimize" takes the parameters "circuit" (the text of the circuit),
+// "format" ("qc" or "qasm", detected from the circuit by default), "output_format"
+// (the input format by default), "pipeline" (DEFAULT_PIPELINE by default) and
+// "limits" ("max_qubits" and "max_gates"), and returns the optimized circuit
+// along with the statistics of the input and optimized circuits.
+// The method "shutdown" stops the server.
+// The errors of the optimization are only recoverable after calling
+// crate::set_recoverable(true), and crate::silence_failures() keeps the panic
+// hook from reporting them.
+#[derive(Default)]
+pub struct Server {
+    pipelines: HashMap<String, Pipeline>,
+    line: String,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server::default()
+    }
+
+    // Answers the requests read from `reader` until the end of the input or a
+    // shutdown request.
+    pub fn serve<R: BufRead, W: Write>(&mut self, mut reader: R, mut writer: W) -> std::io::Result<()> {
+        loop {
+            let mut line = std::mem::take(&mut self.line);
+            line.clear();
+            if reader.read_line(&mut line)? == 0 { return Ok(()); }
+            if line.trim().is_empty() { self.line = line; continue; }
+            let (response, shutdown) = self.handle(&line);
+            self.line = line;
+            writeln!(writer, "{}", response)?;
+            writer.flush()?;
+            if shutdown { return Ok(()); }
+        }
+    }
+
+    // Returns the response to a request, and whether the server should stop.
+    pub fn handle(&mut self, request: &str) -> (Value, bool) {
+        let request: Value = match serde_json::from_str(request) {
+            Ok(request) => request,
+            Err(e) => return (error(Value::Null, -32700, &format!("Parse error: {}", e)), false),
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        match request.get("method").and_then(Value::as_str) {
+            Some("optimize") => {
+                let params = request.get("params").cloned().unwrap_or(json!({}));
+                match catch_unwind(AssertUnwindSafe(|| self.optimize(&params))) {
+                    Ok(Ok(result)) => (json!({"jsonrpc": "2.0", "id": id, "result": result}), false),
+                    Ok(Err(message)) => (error(id, -32602, &message), false),
+                    Err(payload) => {
+                        let message = match payload.downcast::<String>() {
+                            Ok(message) => *message,
+                            Err(payload) => payload.downcast_ref::<&str>().map_or("Optimization failed".to_string(), |m| m.to_string()),
+                        };
+                        (error(id, -32000, &message), false)
+                    },
+                }
+            },
+            Some("shutdown") => (json!({"jsonrpc": "2.0", "id": id, "result": null}), true),
+            Some(method) => (error(id, -32601, &format!("Method not found: {}", method)), false),
+            None => (error(id, -32600, "Invalid request"), false),
+        }
+    }
+
+     fn optimize(&mut self, params: &Value) -> Result<Value, String> {
+        let text = params.get("circuit").and_then(Value::as_str).ok_or("Missing circuit")?;
+        let format = match params.get("format").and_then(Value::as_str) {
+            None => Circuit::detect_format(text),
+            Some("qc") => "qc",
+            Some("qasm") => "qasm",
+            Some(format) => return Err(format!("Format not implemented: {}", format)),
+        };
+        let output_format = match params.get("output_format").and_then(Value::as_str) {
+            None => format,
+            Some("qc") => "qc",
+            Some("qasm") => "qasm",
+            Some(format) => return Err(format!("Format not implemented: {}", format)),
+        };
+        let limit = |name: &str| match params.get("limits").and_then(|limits| limits.get(name)) {
+            None => Ok(usize::MAX),
+            Some(limit) => limit.as_u64().map(|limit| limit as usize).ok_or(format!("Invalid limit: {}", name)),
+        };
+        let (max_qubits, max_gates) = (limit("max_qubits")?, limit("max_gates")?);
+        let (c, header, qubits_mapping) = if format == "qasm" { Circuit::read_qasm(text) } else { Circuit::read_qc(text.as_bytes()) };
+        if c.nb_qubits > max_qubits {
+            return Err(format!("The circuit uses {} qubits, more than the limit of {}", c.nb_qubits, max_qubits));
+        }
+        if c.circ.len() > max_gates {
+            return Err(format!("The circuit has {} gates, more than the limit of {}", c.circ.len(), max_gates));
+        }
+        let spec = params.get("pipeline").and_then(Value::as_str).unwrap_or(DEFAULT_PIPELINE);
+        let pipeline = self.pipelines.entry(spec.to_string()).or_insert_with(|| Pipeline::parse(spec));
+        let (inputs, _) = c.io_qubits(&header, &qubits_mapping);
+        let input_stats = c.decompose_tof().stats_with_inputs(&inputs);
+        let c = pipeline.run(c);
+        let mut output = Vec::new();
+        if output_format == "qasm" { c.write_qasm(&mut output); }
+        else { c.write_qc(&mut output, header, qubits_mapping); }
+        Ok(json!({
+            "circuit": String::from_utf8(output).unwrap(),
+            "format": output_format,
+            "statistics": {"input": input_stats.to_json(), "optimized": c.stats().to_json()},
+        }))
+    }
+}
+
+ fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+// Listens on the Unix socket `path`, each connection being served by its own
+// thread until it is closed or sends a shutdown request.
+#[cfg(unix)]
+pub fn serve_socket(path: &str) -> std::io::Result<()> {
+    let listener = std::os::unix::net::UnixListener::bind(path)?;
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            scope.spawn(move || -> std::io::Result<()> {
+                Server::new().serve(std::io::BufReader::new(stream.try_clone()?), stream)
+            });
+        }
+        Ok(())
+    })
+}
+
+#[cfg(not(unix))]
+pub fn serve_socket(_path: &str) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "The Unix sockets are not available on this platform"))
+}
@@ -1,8 +1,12 @@
+use crate::fail;
 use crate::tableau::{Tableau, TableauColumnMajor};
 use crate::circuit::Circuit;
 use std::collections::HashMap;
 
 pub fn bb_merge(c_in: Circuit) -> Circuit {
+    if c_in.has_barriers() {
+        return c_in.map_unitary_parts(bb_merge);
+    }
     let nb_qubits = c_in.nb_qubits;
     let v = rank_vector(&c_in);
     let mut r = vec![1; v.len()];
@@ -45,7 +49,7 @@ pub fn bb_merge(c_in: Circuit) -> Circuit {
                 pauli_products.push(p.clone());
                 t += 1;
             },
-            _ => {println!("Operator not implemented: {}", gate); std::process::exit(1)},
+            _ => {fail!("Operator not implemented: {}", gate)},
         }
     }
     let mut c = Circuit::new(nb_qubits);
@@ -64,6 +68,9 @@ pub fn bb_merge(c_in: Circuit) -> Circuit {
 }
 
 pub fn fast_t_merge(c_in: Circuit) -> Circuit {
+    if c_in.has_barriers() {
+        return c_in.map_unitary_parts(fast_t_merge);
+    }
     let nb_qubits = c_in.nb_qubits;
     let v = rank_vector(&c_in);
     let mut w = v.clone();
@@ -123,7 +130,7 @@ pub fn fast_t_merge(c_in: Circuit) -> Circuit {
                 pauli_products.push(p.clone());
                 t += 1;
             },
-            _ => {println!("Operator not implemented: {}", gate); std::process::exit(1)},
+            _ => {fail!("Operator not implemented: {}", gate)},
         }
     }
     let mut c = Circuit::new(c_in.nb_qubits);
@@ -178,7 +185,7 @@ pub fn fast_t_merge(c_in: Circuit) -> Circuit {
             "s" => { tab.prepend_s(q[0]); tab.prepend_z(q[0]); },
             "cx" => { tab.prepend_cx(q.to_vec()); },
             "t" | "ccz" | "tof" => continue,
-            _ => {println!("Operator not implemented: {}", gate); std::process::exit(1)},
+            _ => {fail!("Operator not implemented: {}", gate)},
         }
     }
     for (gate, q) in c_in.circ.iter().rev() {
@@ -191,7 +198,7 @@ pub fn fast_t_merge(c_in: Circuit) -> Circuit {
             "t" => { diagonalize_pauli_rotation(&mut tab, q[0]); },
             "tof" => { diagonalize_tof(&mut tab, q.to_vec(), true); },
             "ccz" => { diagonalize_tof(&mut tab, q.to_vec(), false); },
-            _ => {println!("Operator not implemented: {}", gate); std::process::exit(1)},
+            _ => {fail!("Operator not implemented: {}", gate)},
         }
     }
     tab
@@ -210,7 +217,7 @@ pub fn rank_vector(c_in: &Circuit) -> Vec::<bool> {
             "t" => { vec.push(diagonalize_pauli_rotation(&mut tab, q[0])); },
             "tof" => { vec.append(&mut diagonalize_tof(&mut tab, q.to_vec(), true)); },
             "ccz" => { vec.append(&mut diagonalize_tof(&mut tab, q.to_vec(), false)); },
-            _ => {println!("Operator not implemented: {}", gate); std::process::exit(1)},
+            _ => {fail!("Operator not implemented: {}", gate)},
         }
     }
     vec
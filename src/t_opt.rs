@@ -1,3 +1,4 @@
+use crate::fail;
 use crate::bit_vector::BitVector;
 use hashbrown::HashMap;
 
@@ -371,3 +372,194 @@ pub fn fast_todd(mut table: Vec<BitVector>, nb_qubits: usize) -> Vec<BitVector>
     }
     table
 }
+
+ fn nullspace(vectors: &[BitVector]) -> Vec<BitVector> {
+    let mut echelon: Vec<(usize, BitVector, BitVector)> = Vec::new();
+    let mut kernel = Vec::new();
+    for (i, v) in vectors.iter().enumerate() {
+        let mut v = v.clone();
+        let mut combination = BitVector::new(vectors.len());
+        combination.xor_bit(i);
+        for (pivot, pivot_vec, pivot_combination) in &echelon {
+            if v.get(*pivot) {
+                v.xor(pivot_vec);
+                combination.xor(pivot_combination);
+            }
+        }
+        if v.popcount() == 0 {
+            kernel.push(combination);
+        }
+        else {
+            echelon.push((v.get_first_one(), v, combination));
+        }
+    }
+    kernel
+}
+
+// TODD algorithm of Heyfron and Campbell. For z = t_i ⊕ t_j, the rows
+// z_a t_b t_c ⊕ z_b t_a t_c ⊕ z_c t_a t_b (a < b < c) are stacked on top of the
+// table, and a vector y of the nullspace of this matrix with y_i ≠ y_j removes
+// at least two columns once z is added to the columns selected by y. In a
+// basis where z is the unit vector of its first one p, these rows reduce to the
+// products t_b t_c with b, c ≠ p. As with FastTODD, TOHPE is run before each
+// search, so that TODD is never worse than TOHPE.
+pub fn todd(mut table: Vec<BitVector>, nb_qubits: usize) -> Vec<BitVector> {
+    'outer: loop {
+        table = tohpe(table, nb_qubits);
+        for i in 0..table.len() {
+            for j in (i+1)..table.len() {
+                let mut z = table[i].clone();
+                z.xor(&table[j]);
+                let z_vec = z.get_boolean_vec();
+                let p = z.get_first_one();
+                let mut matrix = table.clone();
+                for col in matrix.iter_mut() {
+                    let mut t_vec = col.get_boolean_vec();
+                    t_vec.truncate(nb_qubits);
+                    if t_vec[p] {
+                        for (t, &z) in t_vec.iter_mut().zip(&z_vec) {
+                            *t ^= z;
+                        }
+                    }
+                    let mut vec = Vec::<bool>::new();
+                    for a in (0..nb_qubits).rev().filter(|&a| a != p) {
+                        for b in (0..a).filter(|&b| b != p) {
+                            vec.push(t_vec[a] && t_vec[b]);
+                        }
+                    }
+                    col.extend_vec(vec, nb_qubits);
+                }
+                if let Some(y) = nullspace(&matrix).into_iter().find(|y| y.get(i) ^ y.get(j)) {
+                    for (l, col) in table.iter_mut().enumerate() {
+                        if y.get(l) {
+                            col.xor(&z);
+                        }
+                    }
+                    if y.popcount() & 1 == 1 {
+                        table.push(z);
+                    }
+                    table = proper(table);
+                    continue 'outer;
+                }
+            }
+        }
+        break;
+    }
+    table
+}
+
+// The exhaustive decoding enumerates the 2^22 codewords of RM(2, 6)*: for a
+// rank of 7, RM(3, 7)* already has 2^64 codewords.
+pub const EXACT_MAX_RANK: usize = 6;
+
+// Minimal T-count by exhaustive decoding of the punctured Reed-Muller code
+// RM(r-4, r)*, where r is the rank of the parity table. Fails on the tables
+// whose rank exceeds EXACT_MAX_RANK.
+pub fn exact(table: Vec<BitVector>, nb_qubits: usize) -> Vec<BitVector> {
+    let table = proper(table);
+    let rank = table.len() - nullspace(&table).len();
+    if rank > EXACT_MAX_RANK {
+        fail!("The exact optimizer supports phase polynomials of rank at most {}, got rank {}", EXACT_MAX_RANK, rank);
+    }
+    let mut basis: Vec<BitVector> = Vec::new();
+    let mut echelon: Vec<(usize, BitVector, u64)> = Vec::new();
+    let mut coordinates = Vec::new();
+    for col in &table {
+        let mut v = col.clone();
+        let mut combination = 0;
+        for (pivot, pivot_vec, pivot_combination) in &echelon {
+            if v.get(*pivot) {
+                v.xor(pivot_vec);
+                combination ^= pivot_combination;
+            }
+        }
+        if v.popcount() == 0 {
+            coordinates.push(combination);
+            continue;
+        }
+        echelon.push((v.get_first_one(), v, combination ^ (1 << basis.len())));
+        coordinates.push(1 << basis.len());
+        basis.push(col.clone());
+    }
+    if rank < 4 {
+        return table;
+    }
+    let word = coordinates.iter().fold(0u64, |w, u| w ^ (1 << (u - 1)));
+    let mut generators = Vec::new();
+    for monomial in 0..(1u64 << rank) {
+        if monomial.count_ones() as usize > rank - 4 { continue; }
+        let mut generator = 0u64;
+        for u in 1..(1u64 << rank) {
+            if u & monomial == monomial {
+                generator ^= 1 << (u - 1);
+            }
+        }
+        generators.push(generator);
+    }
+    let mut best = word;
+    let mut codeword = 0u64;
+    for k in 1..(1u64 << generators.len()) {
+        codeword ^= generators[k.trailing_zeros() as usize];
+        if (word ^ codeword).count_ones() < best.count_ones() {
+            best = word ^ codeword;
+        }
+    }
+    let mut new_table = Vec::new();
+    for u in 1..(1u64 << rank) {
+        if best & (1 << (u - 1)) == 0 { continue; }
+        let mut col = BitVector::new(nb_qubits);
+        for (k, b) in basis.iter().enumerate() {
+            if u & (1 << k) != 0 {
+                col.xor(b);
+            }
+        }
+        new_table.push(col);
+    }
+    new_table
+}
+
+// Optimizer of phase polynomials: returns a parity table implementing the same
+// phase polynomial as `table` up to a Clifford operator, which is recovered by
+// PhasePolynomial::clifford_correction.
+pub trait PhasePolynomialOptimizer: Send + Sync {
+    fn name(&self) -> String;
+    fn optimize(&self, table: Vec<BitVector>, nb_qubits: usize) -> Vec<BitVector>;
+}
+
+pub struct Tohpe;
+
+impl PhasePolynomialOptimizer for Tohpe {
+    fn name(&self) -> String { "TOHPE".to_string() }
+    fn optimize(&self, table: Vec<BitVector>, nb_qubits: usize) -> Vec<BitVector> { tohpe(table, nb_qubits) }
+}
+
+pub struct FastTodd;
+
+impl PhasePolynomialOptimizer for FastTodd {
+    fn name(&self) -> String { "FastTODD".to_string() }
+    fn optimize(&self, table: Vec<BitVector>, nb_qubits: usize) -> Vec<BitVector> { fast_todd(table, nb_qubits) }
+}
+
+pub struct Todd;
+
+impl PhasePolynomialOptimizer for Todd {
+    fn name(&self) -> String { "TODD".to_string() }
+    fn optimize(&self, table: Vec<BitVector>, nb_qubits: usize) -> Vec<BitVector> { todd(table, nb_qubits) }
+}
+
+pub struct Exact;
+
+impl PhasePolynomialOptimizer for Exact {
+    fn name(&self) -> String { "Exact".to_string() }
+    fn optimize(&self, table: Vec<BitVector>, nb_qubits: usize) -> Vec<BitVector> { exact(table, nb_qubits) }
+}
+
+pub fn optimizer_from_name(optimizer: &str) -> Box<dyn PhasePolynomialOptimizer> {
+    match optimizer {
+        "TOHPE" => Box::new(Tohpe),
+        "FastTODD" => Box::new(FastTodd),
+        "TODD" => Box::new(Todd),
+        "Exact" => Box::new(Exact),
+        _ => { fail!("Optimizer not implemented: {}", optimizer) },
+    }
+}
@@ -1,6 +1,8 @@
+use crate::fail;
 use crate::bit_vector::BitVector;
 use crate::pauli_product::PauliProduct;
 use crate::circuit::Circuit;
+use crate::architecture::Architecture;
 
 #[derive(Debug, Clone)]
 pub struct Tableau {
@@ -218,6 +220,269 @@ impl Tableau {
         }
         c
     }
+
+    pub fn to_circ_arch(&self, inverse: bool, arch: &Architecture) -> Circuit {
+        let mut tab = self.clone();
+        let mut c = Circuit::new(self.nb_qubits);
+        let mut remaining = vec![true; self.nb_qubits];
+        for i in arch.elimination_order() {
+            let ones: Vec<usize> = (0..self.nb_qubits).filter(|&j| remaining[j] && tab.x[j].get(i)).collect();
+            if !ones.is_empty() {
+                for (a, b) in arch.steiner_reduce(i, &ones, &remaining) {
+                    tab.append_cx(vec![a, b]);
+                    c.circ.push(("cx".into(), vec![a, b]));
+                }
+                if tab.z[i].get(i) {
+                    tab.append_s(i);
+                    c.circ.push(("s".into(), vec![i]));
+                }
+                tab.append_h(i);
+                c.circ.push(("h".into(), vec![i]));
+            }
+            let ones: Vec<usize> = (0..self.nb_qubits).filter(|&j| remaining[j] && tab.z[j].get(i)).collect();
+            for (a, b) in arch.steiner_reduce(i, &ones, &remaining) {
+                tab.append_cx(vec![b, a]);
+                c.circ.push(("cx".into(), vec![b, a]));
+            }
+            let ones: Vec<usize> = (0..self.nb_qubits).filter(|&j| remaining[j] && j != i && tab.z[j].get(i + self.nb_qubits)).collect();
+            for j in ones {
+                if tab.x[j].get(i + self.nb_qubits) {
+                    tab.append_s(j);
+                    c.circ.push(("s".into(), vec![j]));
+                }
+                else {
+                    tab.append_h(j);
+                    c.circ.push(("h".into(), vec![j]));
+                }
+            }
+            let ones: Vec<usize> = (0..self.nb_qubits).filter(|&j| remaining[j] && tab.x[j].get(i + self.nb_qubits)).collect();
+            for (a, b) in arch.steiner_reduce(i, &ones, &remaining) {
+                tab.append_cx(vec![a, b]);
+                c.circ.push(("cx".into(), vec![a, b]));
+            }
+            if tab.z[i].get(i + self.nb_qubits) {
+                tab.append_s(i);
+                c.circ.push(("s".into(), vec![i]));
+            }
+            if tab.signs.get(i) {
+                tab.append_x(i);
+                c.circ.push(("x".into(), vec![i]));
+            }
+            if tab.signs.get(i + self.nb_qubits) {
+                tab.append_z(i);
+                c.circ.push(("z".into(), vec![i]));
+            }
+            remaining[i] = false;
+        }
+        Tableau::orient(c, inverse)
+    }
+
+    // "Best" is CNOT-optimal among the syntheses: it keeps the circuit with the
+    // fewest CNOT gates, then the fewest Hadamard gates, then the lowest depth,
+    // even if another synthesis uses fewer Hadamard gates. "MinimalH" keeps the
+    // circuit with the fewest Hadamard gates, then CNOT gates, then the lowest
+    // depth: as the canonical synthesis is among the candidates, its H-count is
+    // `min_h_count`. The minimum holds for each Clifford operator taken alone,
+    // not for the whole optimized circuit.
+    pub fn to_circ_with(&self, inverse: bool, synthesis: &str) -> Circuit {
+        match synthesis {
+            "Elimination" => self.to_circ(inverse),
+            "Greedy" => Tableau::orient(self.greedy_synthesis(), inverse),
+            "Canonical" => Tableau::orient(self.canonical_synthesis(), inverse),
+            "Best" => {
+                ["Elimination", "Greedy", "Canonical"].iter()
+                    .map(|s| self.to_circ_with(inverse, s))
+                    .min_by_key(|c| (c.cnot_count(), c.get_statistics().0, c.depth()))
+                    .unwrap()
+            },
+            "MinimalH" => {
+                ["Elimination", "Greedy", "Canonical"].iter()
+                    .map(|s| self.to_circ_with(inverse, s))
+                    .min_by_key(|c| (c.get_statistics().0, c.cnot_count(), c.depth()))
+                    .unwrap()
+            },
+            _ => { fail!("Clifford synthesis not implemented: {}", synthesis) },
+        }
+    }
+
+    // Every Hadamard gate increases the rank of the X part of the stabilizers
+    // by at most one, and Hadamard-free gates preserve it, so this rank is a
+    // lower bound on the H-count. The canonical synthesis reaches it.
+    pub fn min_h_count(&self) -> usize {
+        let mut rows: Vec<BitVector> = self.x.iter().map(|x| {
+            let mut bv = BitVector::new(self.nb_qubits);
+            for i in 0..self.nb_qubits {
+                if x.get(i) { bv.xor_bit(i); }
+            }
+            bv
+        }).collect();
+        let mut rank = 0;
+        for i in 0..self.nb_qubits {
+            if let Some(pivot) = (rank..rows.len()).find(|&j| rows[j].get(i)) {
+                rows.swap(rank, pivot);
+                let row = rows[rank].clone();
+                for r in rows.iter_mut().skip(rank + 1) {
+                    if r.get(i) { r.xor(&row); }
+                }
+                rank += 1;
+            }
+        }
+        rank
+    }
+
+    // Circuits built by appending gates until the tableau is the identity
+    // implement the inverse of the tableau.
+     fn orient(c: Circuit, inverse: bool) -> Circuit {
+        if !inverse {
+            let mut c2 = Circuit::new(c.nb_qubits);
+            for (gate, qubits) in c.circ.into_iter().rev() {
+                c2.circ.push((gate.to_string(), qubits.to_vec()));
+                if gate == "s" { c2.circ.push(("z".into(), qubits.to_vec())); }
+            }
+            return c2;
+        }
+        c
+    }
+
+     fn push_gate(&mut self, c: &mut Circuit, gate: &str, qubits: Vec<usize>) {
+        match gate {
+            "h" => self.append_h(qubits[0]),
+            "s" => self.append_s(qubits[0]),
+            "x" => self.append_x(qubits[0]),
+            "z" => self.append_z(qubits[0]),
+            "cx" => self.append_cx(qubits.to_vec()),
+            _ => unreachable!(),
+        }
+        c.circ.push((gate.into(), qubits));
+    }
+
+     fn push_cz(&mut self, c: &mut Circuit, a: usize, b: usize) {
+        self.push_gate(c, "cx", vec![a, b]);
+        self.push_gate(c, "s", vec![b]);
+        self.push_gate(c, "cx", vec![a, b]);
+        self.push_gate(c, "s", vec![a]);
+        self.push_gate(c, "z", vec![a]);
+        self.push_gate(c, "s", vec![b]);
+        self.push_gate(c, "z", vec![b]);
+    }
+
+     fn fix_signs(&mut self, c: &mut Circuit, i: usize) {
+        if self.signs.get(i) {
+            self.push_gate(c, "x", vec![i]);
+        }
+        if self.signs.get(i + self.nb_qubits) {
+            self.push_gate(c, "z", vec![i]);
+        }
+    }
+
+     fn column_weight(&self, col: usize) -> usize {
+        (0..self.nb_qubits).filter(|&j| self.x[j].get(col) || self.z[j].get(col)).count()
+    }
+
+    // Greedy decoupling in the style of Bravyi et al.: the qubit whose
+    // stabilizer and destabilizer have the smallest support is decoupled first,
+    // using single-qubit Cliffords to turn both Pauli products into Z and X
+    // strings that are cleared with CNOT fans.
+     fn greedy_synthesis(&self) -> Circuit {
+        let mut tab = self.clone();
+        let n = self.nb_qubits;
+        let mut c = Circuit::new(n);
+        let mut remaining: Vec<usize> = (0..n).collect();
+        while !remaining.is_empty() {
+            let index = (0..remaining.len()).min_by_key(|&k| tab.column_weight(remaining[k]) + tab.column_weight(remaining[k] + n)).unwrap();
+            let i = remaining.swap_remove(index);
+            for j in 0..n {
+                if tab.x[j].get(i) {
+                    if tab.z[j].get(i) { tab.push_gate(&mut c, "s", vec![j]); }
+                    tab.push_gate(&mut c, "h", vec![j]);
+                }
+            }
+            if !tab.z[i].get(i) {
+                let pivot = tab.z.iter().position(|z| z.get(i)).unwrap();
+                tab.push_gate(&mut c, "cx", vec![i, pivot]);
+            }
+            for j in 0..n {
+                if tab.z[j].get(i) && j != i {
+                    tab.push_gate(&mut c, "cx", vec![j, i]);
+                }
+            }
+            for j in 0..n {
+                if tab.z[j].get(i + n) {
+                    if tab.x[j].get(i + n) { tab.push_gate(&mut c, "s", vec![j]); }
+                    else { tab.push_gate(&mut c, "h", vec![j]); }
+                }
+            }
+            for j in 0..n {
+                if tab.x[j].get(i + n) && j != i {
+                    tab.push_gate(&mut c, "cx", vec![i, j]);
+                }
+            }
+            tab.fix_signs(&mut c, i);
+        }
+        c
+    }
+
+    // Canonical form F1 H F2 where F1 and F2 are Hadamard-free, each written as
+    // a CNOT layer followed by a CZ and S layer, and H is a layer of Hadamard
+    // gates.
+     fn canonical_synthesis(&self) -> Circuit {
+        let mut tab = self.clone();
+        let n = self.nb_qubits;
+        let mut c = Circuit::new(n);
+        let mut pivots: Vec<(usize, usize)> = Vec::new();
+        for col in 0..n {
+            let row = match (0..n).find(|&j| tab.x[j].get(col) && pivots.iter().all(|&(r, _)| r != j)) {
+                Some(row) => row,
+                None => continue,
+            };
+            for j in 0..n {
+                if j != row && tab.x[j].get(col) {
+                    tab.push_gate(&mut c, "cx", vec![row, j]);
+                }
+            }
+            pivots.push((row, col));
+        }
+        for a in 0..pivots.len() {
+            for b in (a+1)..pivots.len() {
+                if tab.z[pivots[a].0].get(pivots[b].1) {
+                    tab.push_cz(&mut c, pivots[a].0, pivots[b].0);
+                }
+            }
+        }
+        for &(row, col) in &pivots {
+            if tab.z[row].get(col) {
+                tab.push_gate(&mut c, "s", vec![row]);
+            }
+        }
+        for &(row, _) in &pivots {
+            tab.push_gate(&mut c, "h", vec![row]);
+        }
+        for col in 0..n {
+            let row = (col..n).find(|&j| tab.z[j].get(col)).unwrap();
+            if row != col {
+                tab.push_gate(&mut c, "cx", vec![col, row]);
+            }
+            for j in 0..n {
+                if j != col && tab.z[j].get(col) {
+                    tab.push_gate(&mut c, "cx", vec![j, col]);
+                }
+            }
+        }
+        for i in 0..n {
+            for j in (i+1)..n {
+                if tab.z[i].get(j + n) {
+                    tab.push_cz(&mut c, i, j);
+                }
+            }
+            if tab.z[i].get(i + n) {
+                tab.push_gate(&mut c, "s", vec![i]);
+            }
+        }
+        for i in 0..n {
+            tab.fix_signs(&mut c, i);
+        }
+        c
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -354,4 +619,52 @@ impl TableauColumnMajor {
         }
         c
     }
+
+    pub fn to_tableau(&self) -> Tableau {
+        let mut tab = Tableau::new(self.nb_qubits);
+        for i in 0..self.nb_qubits {
+            tab.insert_pauli_product(self.stabs[i].clone(), i);
+            tab.insert_pauli_product(self.destabs[i].clone(), i + self.nb_qubits);
+        }
+        tab
+    }
+
+    pub fn to_circ_arch(&self, inverse: bool, arch: &Architecture) -> Circuit {
+        self.to_tableau().to_circ_arch(inverse, arch)
+    }
+
+    pub fn to_circ_with(&self, inverse: bool, synthesis: &str) -> Circuit {
+        if synthesis == "Elimination" {
+            return self.to_circ(inverse);
+        }
+        self.to_tableau().to_circ_with(inverse, synthesis)
+    }
+
+    // Basis, in row echelon form, of the parities whose rotations are mapped to
+    // diagonal operators by this Clifford operator, i.e. the Z-type Pauli
+    // products spanned by the stabilizers.
+    pub fn diagonal_parities(&self) -> Vec<BitVector> {
+        let mut rows: Vec<(BitVector, BitVector)> = self.stabs.iter().map(|p| (p.x.clone(), p.z.clone())).collect();
+        let mut pivot = 0;
+        for col in 0..self.nb_qubits {
+            let Some(k) = (pivot..rows.len()).find(|&k| rows[k].0.get(col)) else { continue };
+            rows.swap(pivot, k);
+            let (x, z) = rows[pivot].clone();
+            for (i, row) in rows.iter_mut().enumerate() {
+                if i != pivot && row.0.get(col) {
+                    row.0.xor(&x);
+                    row.1.xor(&z);
+                }
+            }
+            pivot += 1;
+        }
+        let mut basis: Vec<BitVector> = Vec::new();
+        for (_, mut z) in rows.into_iter().skip(pivot) {
+            for b in &basis {
+                if z.get(b.get_first_one()) { z.xor(b); }
+            }
+            basis.push(z);
+        }
+        basis
+    }
 }
@@ -0,0 +1,123 @@
+mod common;
+
+use common::{bundled, equal_up_to_phase, equivalent, grid, line, random_circuit, run, to_tableau, Random, CLIFFORD_GATES};
+use quantum_circuit_optimization::architecture::Architecture;
+use quantum_circuit_optimization::bit_vector::BitVector;
+use quantum_circuit_optimization::circuit::Circuit;
+use quantum_circuit_optimization::phase_polynomial::PhasePolynomial;
+use quantum_circuit_optimization::t_opt::{Tohpe, Todd};
+
+ fn architectures() -> Vec<(&'static str, Architecture)> {
+    vec![("line", line(6)), ("grid", grid(2, 3))]
+}
+
+ fn assert_coupled(c: &Circuit, arch: &Architecture, context: &str) {
+    for (gate, qubits) in &c.circ {
+        if gate == "cx" { assert!(arch.coupling[qubits[0]][qubits[1]], "{}: CNOT between {:?}", context, qubits); }
+    }
+}
+
+#[test]
+fn elimination_order_keeps_the_graph_connected() {
+    for (name, arch) in architectures() {
+        let order = arch.elimination_order();
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..arch.nb_qubits).collect::<Vec<_>>(), "{}", name);
+        for k in 0..order.len() {
+            let mut remaining = Architecture::new(arch.nb_qubits - k);
+            let qubits = &order[k..];
+            for (i, &a) in qubits.iter().enumerate() {
+                for (j, &b) in qubits.iter().enumerate() {
+                    if i < j && arch.coupling[a][b] { remaining.add_edge(i, j); }
+                }
+            }
+            assert!(remaining.is_connected(), "{}: disconnected after removing {:?}", name, &order[..k]);
+        }
+    }
+}
+
+#[test]
+fn steiner_parity_accumulates_the_terminals_on_the_root() {
+    for (name, arch) in architectures() {
+        let allowed = vec![true; arch.nb_qubits];
+        let mut random = Random::new(1);
+        for _ in 0..30 {
+            let root = random.next(arch.nb_qubits);
+            let mut terminals = vec![root];
+            for q in 0..arch.nb_qubits {
+                if q != root && random.next(2) == 1 { terminals.push(q); }
+            }
+            let edges = arch.steiner_tree(root, &terminals, &allowed);
+            for &(p, c) in &edges {
+                assert!(arch.coupling[p][c], "{}: tree edge {:?}", name, (p, c));
+            }
+            let mut c = Circuit::new(arch.nb_qubits);
+            c.circ = arch.steiner_parity(root, &terminals, &allowed);
+            assert_coupled(&c, &arch, name);
+            // The root of the output holds the parity of the terminals of the input
+            for input in 0..1 << arch.nb_qubits {
+                let state = run(&c, input);
+                let output = state.iter().position(|a| a.0.abs() > 0.5).unwrap();
+                let parity = terminals.iter().filter(|&&t| input >> t & 1 == 1).count() % 2;
+                assert_eq!(output >> root & 1, parity, "{}: root {}, terminals {:?}", name, root, terminals);
+            }
+        }
+    }
+}
+
+#[test]
+fn clifford_synthesis_follows_the_architecture() {
+    for (name, arch) in architectures() {
+        for seed in 0..20 {
+            let c = random_circuit(arch.nb_qubits, 60, &CLIFFORD_GATES, seed);
+            let tab = to_tableau(&c);
+            let synthesized = tab.to_circ_arch(false, &arch);
+            assert_coupled(&synthesized, &arch, name);
+            assert!(equivalent(&synthesized, &c, arch.nb_qubits), "{}: seed {}", name, seed);
+            let mut identity = c.clone();
+            identity.append(tab.to_circ_arch(true, &arch).circ);
+            let states: Vec<_> = (0..1 << arch.nb_qubits).map(|i| run(&identity, i)).collect();
+            let inputs: Vec<_> = (0..1 << arch.nb_qubits).map(|i| run(&Circuit::new(arch.nb_qubits), i)).collect();
+            assert!(equal_up_to_phase(&states, &inputs), "{}: inverse, seed {}", name, seed);
+        }
+    }
+}
+
+#[test]
+fn phase_polynomial_synthesis_follows_the_architecture() {
+    for (name, arch) in architectures() {
+        let mut random = Random::new(2);
+        let mut p = PhasePolynomial::new(arch.nb_qubits);
+        // The empty parity only contributes a global phase
+        p.table.push(BitVector::new(arch.nb_qubits));
+        for _ in 0..20 {
+            let mut z = BitVector::new(arch.nb_qubits);
+            for q in 0..arch.nb_qubits {
+                if random.next(2) == 1 { z.xor_bit(q); }
+            }
+            p.table.push(z);
+        }
+        let synthesized = p.to_circ_arch(&arch);
+        assert_coupled(&synthesized, &arch, name);
+        assert_eq!(synthesized.get_statistics().2, p.table.iter().filter(|z| z.popcount() > 0).count(), "{}", name);
+        let mut reference = p.clone();
+        reference.table.retain(|z| z.popcount() > 0);
+        assert!(equivalent(&synthesized, &reference.to_circ(), arch.nb_qubits), "{}", name);
+    }
+}
+
+#[test]
+fn t_opt_follows_the_architecture() {
+    for (name, arch) in architectures() {
+        for circuit in ["tof_3", "barenco_tof_3", "mod5_4"] {
+            let mut c = bundled(circuit).decompose_tof();
+            c.nb_qubits = arch.nb_qubits;
+            for optimized in [c.t_opt_arch(&Tohpe, &arch), c.t_opt_arch(&Todd, &arch)] {
+                assert_coupled(&optimized, &arch, name);
+                assert!(equivalent(&optimized, &c, arch.nb_qubits), "{}: {} not equivalent", name, circuit);
+                assert!(optimized.get_statistics().2 <= c.get_statistics().2, "{}: {}", name, circuit);
+            }
+        }
+    }
+}
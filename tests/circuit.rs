@@ -0,0 +1,80 @@
+mod common;
+
+use common::from_qc;
+use quantum_circuit_optimization::circuit::Circuit;
+
+ fn to_qc(text: &str) -> String {
+    let (c, header, map) = Circuit::read_qc(text.as_bytes());
+    let mut output = Vec::new();
+    c.write_qc(&mut output, header, map);
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn toffoli_gates_are_written() {
+    let text = ".v a b c\nBEGIN\ntof a b c\nZ a b c\ntof a b\nEND";
+    assert_eq!(to_qc(text), ".v a b c\n\nBEGIN\ntof a b c\nZ a b c\ncnot a b\nEND");
+    let c = from_qc(text);
+    assert_eq!(from_qc(&to_qc(text)).circ, c.circ);
+}
+
+#[test]
+fn dynamic_circuits_are_parsed() {
+    let text = ".v a b c\nBEGIN\nH a\nmeasure a\nif a X b\nif a cnot b c\nif a S* c\nreset a\nEND";
+    let c = from_qc(text);
+    let gates: Vec<(&str, Vec<usize>)> = c.circ.iter().map(|(gate, qubits)| (&gate[..], qubits.to_vec())).collect();
+    assert_eq!(gates, [("h", vec![0]), ("measure", vec![0]), ("if_x", vec![0, 1]), ("if_cx", vec![0, 1, 2]),
+        ("if_z", vec![0, 2]), ("if_s", vec![0, 2]), ("reset", vec![0])]);
+    assert!(c.has_barriers());
+    assert_eq!(from_qc(&to_qc(text)).circ, c.circ);
+}
+
+#[test]
+fn dynamic_circuits_are_written_in_qasm() {
+    let c = from_qc(".v a b c\nBEGIN\nH a\nmeasure a\nif a X b\nif a tof a b c\nreset a\nEND");
+    let mut output = Vec::new();
+    c.write_qasm(&mut output);
+    let (read, _, _) = Circuit::read_qasm(&String::from_utf8(output).unwrap());
+    assert_eq!(read.circ, c.circ);
+}
+
+#[test]
+fn repeated_measurements_declare_a_single_register() {
+    let c = from_qc(".v a b\nBEGIN\nmeasure a\nreset a\nH a\nmeasure a\nif a X b\nmeasure b\nEND");
+    let mut output = Vec::new();
+    c.write_qasm(&mut output);
+    let qasm = String::from_utf8(output).unwrap();
+    assert_eq!(qasm.matches("creg c0[1];").count(), 1);
+    assert_eq!(qasm.matches("creg c1[1];").count(), 1);
+    let (read, _, _) = Circuit::read_qasm(&qasm);
+    assert_eq!(read.circ, c.circ);
+}
+
+#[test]
+#[should_panic(expected = "Unknown qubit: d")]
+fn conditions_on_unknown_qubits_are_rejected() {
+    quantum_circuit_optimization::set_recoverable(true);
+    from_qc(".v a b c\nBEGIN\nif d X b\nEND");
+}
+
+#[test]
+#[should_panic(expected = "Operator not implemented: measure")]
+fn classically_controlled_measurements_are_rejected() {
+    quantum_circuit_optimization::set_recoverable(true);
+    from_qc(".v a b\nBEGIN\nif a measure b\nEND");
+}
+
+#[test]
+fn statistics_of_a_small_circuit() {
+    let (c, header, map) = Circuit::read_qc(".v a b c\n.i a b\nBEGIN\nH a\nT a\ncnot a b\nT b\ncnot b c\nT c\nS a\nEND".as_bytes());
+    let (inputs, _) = c.io_qubits(&header, &map);
+    let stats = c.stats_with_inputs(&inputs);
+    assert_eq!((stats.qubits, stats.ancillas, stats.gates), (3, 1, 7));
+    assert_eq!((stats.depth, stats.t_depth, stats.two_qubit_depth), (6, 3, 2));
+    assert_eq!((stats.t_count, stats.h_count, stats.cnot_count, stats.hadamard_free_slices), (3, 1, 2, 1));
+    let histogram: Vec<(&str, usize)> = stats.histogram.iter().map(|(gate, &count)| (gate.as_str(), count)).collect();
+    assert_eq!(histogram, [("cx", 2), ("h", 1), ("s", 1), ("t", 3)]);
+    // Without the .i line, every qubit is an input
+    assert_eq!(c.stats().ancillas, 0);
+    assert_eq!(from_qc(".v a b c\nBEGIN\nH a\nEND").stats_with_inputs(&[true; 3]).ancillas, 0);
+}
@@ -0,0 +1,138 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use quantum_circuit_optimization::circuit::Circuit;
+
+ fn run_binary(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_quantum_circuit_optimization")).args(args).output().unwrap()
+}
+
+ fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("qco_cli_{}_{}", std::process::id(), name))
+}
+
+// Runs the binary with the given text on its standard input.
+ fn run_binary_with_input(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_quantum_circuit_optimization")).args(args)
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// Runs the binary expecting a failure whose message contains `error`.
+ fn rejects(args: &[&str], error: &str) {
+    let output = run_binary(args);
+    assert!(!output.status.success(), "{:?} accepted", args);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(error), "{:?}: expected {:?} in {}", args, error, stdout);
+}
+
+#[test]
+fn unknown_options_are_rejected() {
+    rejects(&["--input", "circuits/inputs/tof_3.qc", "--frobnicate"], "Unknown option: --frobnicate");
+    rejects(&["circuits/inputs/tof_3.qc", "frobnicate"], "Unknown option: frobnicate");
+    rejects(&["circuits/inputs/tof_3.qc", "frobnicate=3"], "Unknown option: frobnicate=3");
+    rejects(&["--input", "circuits/inputs/tof_3.qc", "--quiet=yes"], "Unknown option: --quiet=yes");
+    rejects(&["--input", "circuits/inputs/tof_3.qc", "--jobs"], "Missing value for the option --jobs");
+    rejects(&["--input", "circuits/inputs/tof_3.qc", "--format", "json"], "Format not implemented: json");
+    rejects(&["--input", "circuits/inputs/tof_3.qc", "circuits/inputs/tof_4.qc"], "The option --input is given several times");
+    rejects(&["--tohpe"], "No input file provided");
+}
+
+#[test]
+fn keywords_are_rejected_with_a_pipeline() {
+    rejects(&["--input", "circuits/inputs/tof_3.qc", "--passes", "fasttodd", "--tohpe"], "A pipeline of passes replaces the options --tohpe");
+    rejects(&["circuits/inputs/tof_3.qc", "passes=fasttodd", "fasttmerge", "bestclifford"],
+        "A pipeline of passes replaces the options --fasttmerge, --bestclifford");
+    rejects(&["circuits/inputs/tof_3.qc", "passes=fasttodd", "budget=2"], "A pipeline of passes replaces the options --budget");
+}
+
+#[test]
+fn positional_shorthand_matches_the_flags() {
+    let (flags, shorthand) = (temp_file("flags.qasm"), temp_file("shorthand.qasm"));
+    let output1 = run_binary(&["--input", "circuits/inputs/tof_3.qc", "--fasttmerge", "--internalhopt", "--tohpe", "--format", "qasm",
+        "--output", flags.to_str().unwrap(), "--quiet", "--stats=json"]);
+    let output2 = run_binary(&["FastTMerge", "circuits/inputs/tof_3.qc", "internalhopt", "TOHPE", "QASM",
+        "--output", shorthand.to_str().unwrap(), "--quiet", "--stats=json"]);
+    assert!(output1.status.success() && output2.status.success());
+    assert_eq!(output1.stdout, output2.stdout);
+    let (text1, text2) = (std::fs::read_to_string(&flags).unwrap(), std::fs::read_to_string(&shorthand).unwrap());
+    std::fs::remove_file(flags).unwrap();
+    std::fs::remove_file(shorthand).unwrap();
+    assert!(text1.starts_with("OPENQASM 2.0;"));
+    assert_eq!(text1, text2);
+}
+
+#[test]
+fn formats_are_detected_from_the_content() {
+    assert_eq!(Circuit::detect_format(".v a b\nBEGIN\ncnot a b\nEND"), "qc");
+    assert_eq!(Circuit::detect_format("# comment\n.v a\nBEGIN\nH a\nEND"), "qc");
+    assert_eq!(Circuit::detect_format("OPENQASM 2.0;\ninclude \"qelib1.inc\";\nqreg q[1];\nh q[0];"), "qasm");
+    assert_eq!(Circuit::detect_format("// generated\n\n  OPENQASM 2.0;\nqreg q[1];"), "qasm");
+    assert_eq!(Circuit::detect_format(""), "qc");
+}
+
+#[test]
+fn standard_input_is_written_to_the_standard_output() {
+    let qc = std::fs::read_to_string("circuits/inputs/tof_3.qc").unwrap();
+    let output = run_binary_with_input(&["-", "--fasttmerge", "--internalhopt", "--tohpe"], &qc);
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(Circuit::detect_format(&text), "qc");
+    let (c, _, _) = Circuit::read_qc(text.as_bytes());
+    assert_eq!(c.get_statistics().2, 13);
+    // The messages do not mix with the circuit
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Running TOHPE algorithm"));
+
+    let mut qasm = Vec::new();
+    Circuit::read_qc(qc.as_bytes()).0.write_qasm(&mut qasm);
+    let output = run_binary_with_input(&["--input", "-", "--fasttmerge", "--internalhopt", "--tohpe", "--quiet"], &String::from_utf8(qasm).unwrap());
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.starts_with("OPENQASM 2.0;"));
+    assert_eq!(Circuit::read_qasm(&text).0.get_statistics().2, 13);
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn standard_output_can_be_chosen_for_a_file() {
+    let output = run_binary(&["circuits/inputs/tof_3.qc", "--output", "-", "--format", "qasm", "--fasttmerge", "--internalhopt", "--tohpe"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("OPENQASM 2.0;"));
+    assert!(!output.stderr.is_empty());
+}
+
+#[test]
+fn errors_are_printed_on_the_standard_error_when_streaming() {
+    let output = run_binary_with_input(&["-", "--tohpe"], ".v a b\nBEGIN\ncnot a c\nEND");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Unknown qubit: c"));
+}
+
+#[test]
+fn batch_reports_the_malformed_files() {
+    let (directory, output) = (temp_file("batch"), temp_file("batch_outputs"));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::copy("circuits/inputs/tof_3.qc", directory.join("a.qc")).unwrap();
+    std::fs::write(directory.join("b.qc"), ".v a b\nBEGIN\ncnot a c\nEND\n").unwrap();
+    std::fs::copy("circuits/inputs/barenco_tof_3.qc", directory.join("c.qc")).unwrap();
+    let result = run_binary(&["--input", directory.to_str().unwrap(), "--output", output.to_str().unwrap(),
+        "--fasttmerge", "--internalhopt", "--jobs", "2"]);
+    std::fs::remove_dir_all(&directory).unwrap();
+    let written: Vec<_> = std::fs::read_dir(&output).unwrap().map(|e| e.unwrap().file_name()).collect();
+    std::fs::remove_dir_all(&output).unwrap();
+    assert!(result.status.success());
+    assert_eq!(written.len(), 2);
+    // The standard output only contains the table
+    let table = String::from_utf8(result.stdout).unwrap();
+    let rows: Vec<&str> = table.lines().collect();
+    assert_eq!(rows.len(), 4, "{}", table);
+    assert!(rows[0].starts_with("file,qubits,") && rows[0].contains(",error"));
+    assert!(rows[1].starts_with("a.qc,5,") && rows[3].starts_with("c.qc,5,"), "{}", table);
+    assert_eq!(rows[1].split(',').count(), rows[0].split(',').count());
+    assert!(rows[2].starts_with("b.qc,") && rows[2].contains("\"Unknown qubit: c\""), "{}", rows[2]);
+    let stderr = String::from_utf8(result.stderr).unwrap();
+    assert!(stderr.contains("Unknown qubit: c") && !stderr.contains("panicked"), "{}", stderr);
+}
@@ -0,0 +1,197 @@
+#![allow(dead_code)]
+
+use quantum_circuit_optimization::architecture::Architecture;
+use quantum_circuit_optimization::circuit::Circuit;
+use quantum_circuit_optimization::tableau::Tableau;
+
+// State vector simulator checking that the transformations preserve the
+// circuits. Measurements are postselected on the outcome 0, as are the resets,
+// so that a circuit with ancillas is simulated in the runs where they are all
+// measured in the state |0>.
+pub type Amplitude = (f64, f64);
+
+pub const EPSILON: f64 = 1e-6;
+
+ fn mul(a: Amplitude, b: Amplitude) -> Amplitude {
+    (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+pub fn apply(state: &mut [Amplitude], gate: &str, qubits: &[usize]) {
+    let mask = |q: &[usize]| q.iter().fold(0, |m, &q| m | 1 << q);
+    let phase = |state: &mut [Amplitude], m: usize, p: Amplitude| {
+        for (i, a) in state.iter_mut().enumerate() {
+            if i & m == m { *a = mul(*a, p); }
+        }
+    };
+    let flip = |state: &mut [Amplitude], controls: usize, target: usize| {
+        for i in 0..state.len() {
+            if i & controls == controls && i & target == 0 { state.swap(i, i | target); }
+        }
+    };
+    let r = std::f64::consts::FRAC_1_SQRT_2;
+    match gate {
+        "h" => {
+            let m = mask(qubits);
+            for i in 0..state.len() {
+                if i & m != 0 { continue; }
+                let (a, b) = (state[i], state[i | m]);
+                state[i] = ((a.0 + b.0) * r, (a.1 + b.1) * r);
+                state[i | m] = ((a.0 - b.0) * r, (a.1 - b.1) * r);
+            }
+        },
+        "x" => flip(state, 0, mask(qubits)),
+        "z" | "ccz" => phase(state, mask(qubits), (-1.0, 0.0)),
+        "s" => phase(state, mask(qubits), (0.0, 1.0)),
+        "t" => phase(state, mask(qubits), (r, r)),
+        "cx" | "tof" | "ccx" => flip(state, mask(&qubits[..qubits.len() - 1]), mask(&qubits[qubits.len() - 1..])),
+        "measure" | "reset" => {
+            let m = mask(qubits);
+            for (i, a) in state.iter_mut().enumerate() {
+                if i & m != 0 { *a = (0.0, 0.0); }
+            }
+        },
+        _ => panic!("Gate not simulated: {}", gate),
+    }
+}
+
+// Final state of the circuit on the given computational basis state.
+pub fn run(c: &Circuit, input: usize) -> Vec<Amplitude> {
+    let mut state = vec![(0.0, 0.0); 1 << c.nb_qubits];
+    state[input] = (1.0, 0.0);
+    for (gate, qubits) in &c.circ {
+        apply(&mut state, gate, qubits);
+    }
+    state
+}
+
+// Whether the two lists of states are equal up to a global phase, common to
+// all the states.
+pub fn equal_up_to_phase(states1: &[Vec<Amplitude>], states2: &[Vec<Amplitude>]) -> bool {
+    let mut phase = None;
+    for (state1, state2) in states1.iter().zip(states2) {
+        if state1.len() != state2.len() { return false; }
+        for (&a, &b) in state1.iter().zip(state2) {
+            let norm = a.0 * a.0 + a.1 * a.1;
+            if (norm.sqrt() - (b.0 * b.0 + b.1 * b.1).sqrt()).abs() > EPSILON { return false; }
+            if norm < EPSILON { continue; }
+            let ratio = ((b.0 * a.0 + b.1 * a.1) / norm, (b.1 * a.0 - b.0 * a.1) / norm);
+            match phase {
+                None => phase = Some(ratio),
+                Some(p) if (p.0 - ratio.0).abs() > EPSILON || (p.1 - ratio.1).abs() > EPSILON => return false,
+                Some(_) => {},
+            }
+        }
+    }
+    states1.len() == states2.len()
+}
+
+// Whether the two families of states are equal up to a global nonzero
+// factor, as the states obtained by postselection are not normalized.
+pub fn equal_up_to_scalar(states1: &[Vec<Amplitude>], states2: &[Vec<Amplitude>]) -> bool {
+    let normalize = |states: &[Vec<Amplitude>]| {
+        let norm = states.iter().flatten().map(|a| a.0 * a.0 + a.1 * a.1).sum::<f64>().sqrt();
+        if norm < EPSILON { return states.to_vec(); }
+        states.iter().map(|state| state.iter().map(|a| (a.0 / norm, a.1 / norm)).collect()).collect::<Vec<_>>()
+    };
+    equal_up_to_phase(&normalize(states1), &normalize(states2))
+}
+
+// Final states of the circuit on the computational basis states of its
+// non-ancilla qubits, the ancillas starting and being postselected in |0>.
+// The states are given on the non-ancilla qubits, in their order.
+pub fn postselected_states(c: &Circuit) -> Vec<Vec<Amplitude>> {
+    let qubits: Vec<usize> = (0..c.nb_qubits).filter(|q| !c.ancillas.contains_key(q)).collect();
+    let index = |bits: usize| qubits.iter().enumerate().fold(0, |i, (k, &q)| i | (bits >> k & 1) << q);
+    (0..1 << qubits.len()).map(|input| {
+        let mut state = run(c, index(input));
+        for &a in c.ancillas.keys() {
+            apply(&mut state, "measure", &[a]);
+        }
+        (0..1 << qubits.len()).map(|output| state[index(output)]).collect()
+    }).collect()
+}
+
+// Whether the circuits are equal up to a global phase on the inputs where the
+// qubits from `nb_inputs` on are in the state |0>.
+pub fn equivalent(c1: &Circuit, c2: &Circuit, nb_inputs: usize) -> bool {
+    let inputs = 0..1 << nb_inputs;
+    let states1: Vec<_> = inputs.clone().map(|i| run(c1, i)).collect();
+    let states2: Vec<_> = inputs.map(|i| run(c2, i)).collect();
+    equal_up_to_phase(&states1, &states2)
+}
+
+// Linear congruential generator, so that the random tests are deterministic.
+pub struct Random(u64);
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random(seed)
+    }
+
+    // Integer drawn uniformly below n.
+    pub fn next(&mut self, n: usize) -> usize {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 33) as usize % n
+    }
+}
+
+pub const CLIFFORD_GATES: [&str; 6] = ["h", "s", "x", "z", "cx", "cx"];
+pub const CLIFFORD_T_GATES: [&str; 11] = ["h", "x", "z", "s", "t", "t", "cx", "cx", "cx", "tof", "ccz"];
+
+// Random circuit whose gates are drawn among the given ones, each acting on
+// distinct random qubits.
+pub fn random_circuit(nb_qubits: usize, nb_gates: usize, gates: &[&str], seed: u64) -> Circuit {
+    let mut random = Random::new(seed);
+    let mut c = Circuit::new(nb_qubits);
+    for _ in 0..nb_gates {
+        let gate = gates[random.next(gates.len())];
+        let arity = match gate { "cx" => 2, "tof" | "ccx" | "ccz" => 3, _ => 1 };
+        let mut qubits = Vec::new();
+        while qubits.len() < arity {
+            let q = random.next(nb_qubits);
+            if !qubits.contains(&q) { qubits.push(q); }
+        }
+        c.circ.push((gate.to_string(), qubits));
+    }
+    c
+}
+
+pub fn from_qc(text: &str) -> Circuit {
+    Circuit::read_qc(text.as_bytes()).0
+}
+
+pub fn bundled(name: &str) -> Circuit {
+    Circuit::from_qc(&format!("circuits/inputs/{}.qc", name)).0
+}
+
+pub fn line(nb_qubits: usize) -> Architecture {
+    let mut arch = Architecture::new(nb_qubits);
+    for q in 1..nb_qubits {
+        arch.add_edge(q - 1, q);
+    }
+    arch
+}
+
+pub fn grid(rows: usize, columns: usize) -> Architecture {
+    let mut arch = Architecture::new(rows * columns);
+    for q in 0..rows * columns {
+        if q % columns > 0 { arch.add_edge(q - 1, q); }
+        if q >= columns { arch.add_edge(q - columns, q); }
+    }
+    arch
+}
+
+// Tableau of a circuit made of Clifford gates.
+pub fn to_tableau(c: &Circuit) -> Tableau {
+    let mut tab = Tableau::new(c.nb_qubits);
+    for (gate, qubits) in &c.circ {
+        match &gate[..] {
+            "h" => tab.append_h(qubits[0]),
+            "s" => tab.append_s(qubits[0]),
+            "x" => tab.append_x(qubits[0]),
+            "z" => tab.append_z(qubits[0]),
+            _ => tab.append_cx(qubits.to_vec()),
+        }
+    }
+    tab
+}
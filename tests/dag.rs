@@ -0,0 +1,87 @@
+mod common;
+
+use common::{from_qc, run, Amplitude, EPSILON};
+use quantum_circuit_optimization::circuit::Circuit;
+use quantum_circuit_optimization::dag::{commute, Dag};
+
+const GATES: [(&str, usize); 9] = [("h", 1), ("x", 1), ("z", 1), ("s", 1), ("t", 1), ("cx", 2), ("tof", 3), ("ccx", 3), ("ccz", 3)];
+
+// Whether the gates commute exactly, global phase included: X and Z commute
+// up to a phase only.
+ fn simulated_commute(gate1: &str, qubits1: &[usize], gate2: &str, qubits2: &[usize]) -> bool {
+    let circuit = |gates: [(&str, &[usize]); 2]| {
+        let mut c = Circuit::new(4);
+        c.circ = gates.iter().map(|(gate, qubits)| (gate.to_string(), qubits.to_vec())).collect();
+        c
+    };
+    let c1 = circuit([(gate1, qubits1), (gate2, qubits2)]);
+    let c2 = circuit([(gate2, qubits2), (gate1, qubits1)]);
+    let equal = |a: &Amplitude, b: &Amplitude| (a.0 - b.0).abs() < EPSILON && (a.1 - b.1).abs() < EPSILON;
+    (0..16).all(|input| run(&c1, input).iter().zip(&run(&c2, input)).all(|(a, b)| equal(a, b)))
+}
+
+// Sequences of distinct qubits among 4.
+ fn placements(nb_qubits: usize) -> Vec<Vec<usize>> {
+    let mut placements: Vec<Vec<usize>> = vec![Vec::new()];
+    for _ in 0..nb_qubits {
+        let mut longer = Vec::new();
+        for p in &placements {
+            for q in (0..4).filter(|q| !p.contains(q)) {
+                longer.push([&p[..], &[q]].concat());
+            }
+        }
+        placements = longer;
+    }
+    placements
+}
+
+#[test]
+fn commutation_oracle_matches_simulation() {
+    for (gate1, n1) in GATES {
+        for (gate2, n2) in GATES {
+            for qubits1 in placements(n1).into_iter().filter(|q| q[0] == 0) {
+                for qubits2 in placements(n2) {
+                    let expected = simulated_commute(gate1, &qubits1, gate2, &qubits2);
+                    let oracle = commute(gate1, &qubits1, gate2, &qubits2);
+                    assert!(!oracle || expected, "{} {:?} and {} {:?} do not commute", gate1, qubits1, gate2, qubits2);
+                    // The oracle only misses the commutations of Hadamard gates.
+                    if gate1 != "h" && gate2 != "h" {
+                        assert_eq!(oracle, expected, "{} {:?} and {} {:?}", gate1, qubits1, gate2, qubits2);
+                    }
+                }
+            }
+        }
+    }
+    assert!(commute("h", &[0], "h", &[0]));
+    assert!(!commute("h", &[0], "x", &[0]));
+}
+
+#[test]
+fn barriers_only_commute_with_disjoint_gates() {
+    for barrier in [("measure", vec![0]), ("reset", vec![0]), ("if_x", vec![1, 0])] {
+        for (gate, qubits) in [("z", vec![0]), ("measure", vec![0]), ("reset", vec![0]), ("cx", vec![0, 2]), ("if_x", vec![1, 0])] {
+            assert!(!commute(barrier.0, &barrier.1, gate, &qubits), "{} and {}", barrier.0, gate);
+        }
+        assert!(commute(barrier.0, &barrier.1, "cx", &[2, 3]));
+    }
+}
+
+#[test]
+fn dag_keeps_the_wires_linked() {
+    let c = from_qc(".v a b c\nBEGIN\nH a\ncnot a b\nT b\ncnot b c\nH c\nEND");
+    let mut dag = Dag::from_circ(&c);
+    assert_eq!(dag.to_circ().circ, c.circ);
+    assert_eq!(dag.next(1, 1), Some(2));
+    assert_eq!(dag.previous(3, 1), Some(2));
+    dag.remove(2);
+    assert_eq!(dag.next(1, 1), Some(3));
+    assert_eq!(dag.previous(3, 1), Some(1));
+    dag.replace(0, "x");
+    let id = dag.push("t", &[0]);
+    assert_eq!(dag.previous(id, 0), Some(1));
+    assert_eq!(dag.len(), 5);
+    let gates: Vec<String> = dag.to_circ().circ.into_iter().map(|(gate, _)| gate).collect();
+    assert_eq!(gates, ["x", "cx", "cx", "h", "t"]);
+    assert_eq!(dag.first, [Some(0), Some(1), Some(3)]);
+    assert_eq!(dag.last, [Some(id), Some(3), Some(4)]);
+}
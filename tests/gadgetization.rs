@@ -0,0 +1,73 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::{apply, bundled, equal_up_to_scalar, postselected_states, run, Amplitude};
+use quantum_circuit_optimization::circuit::Circuit;
+use quantum_circuit_optimization::pass::Pipeline;
+use quantum_circuit_optimization::set_quiet;
+
+#[test]
+fn budget_bounds_the_ancillas() {
+    set_quiet(true);
+    let c = Pipeline::parse("fasttmerge,internalhopt").run(bundled("tof_3"));
+    let internal_h_count = c.get_statistics().1;
+    assert_eq!(internal_h_count, 2);
+    let mut t_counts = Vec::new();
+    for budget in [0, 1, 2, 1000] {
+        let gadgetized = c.hadamard_gadgetization_budget(budget);
+        assert!(gadgetized.ancillas.len() <= budget, "budget {}: {} ancillas", budget, gadgetized.ancillas.len());
+        assert_eq!(gadgetized.nb_qubits, c.nb_qubits + gadgetized.ancillas.len());
+        assert!(equal_up_to_scalar(&postselected_states(&gadgetized), &postselected_states(&c)), "budget {}", budget);
+        t_counts.push(Pipeline::parse("tohpe").run(gadgetized).get_statistics().2);
+    }
+    assert_eq!(c.hadamard_gadgetization_budget(1000).ancillas.len(), internal_h_count);
+    for k in 1..t_counts.len() {
+        assert!(t_counts[k] <= t_counts[k - 1], "T-counts {:?}", t_counts);
+    }
+    assert!(t_counts[3] < t_counts[0], "T-counts {:?}", t_counts);
+}
+
+// Final states of the circuit followed by the feed-forward corrections, on
+// the computational basis states of the first `nb_inputs` qubits, in the run
+// where the ancillas are measured with the given outcomes. The states are
+// given on the first `nb_inputs` qubits.
+ fn branch_states(c: &Circuit, corrections: &Circuit, nb_inputs: usize, outcomes: &HashMap<usize, bool>) -> Vec<Vec<Amplitude>> {
+    let ancilla_bits = outcomes.iter().fold(0, |i, (&a, &b)| i | (b as usize) << a);
+    (0..1 << nb_inputs).map(|input| {
+        let mut state = run(c, input);
+        for (gate, qubits) in &corrections.circ {
+            match gate.strip_prefix("if_") {
+                Some(gate) => if outcomes[&qubits[0]] { apply(&mut state, gate, &qubits[1..]) },
+                None => {
+                    // Projection on the outcome of the measured ancilla
+                    let flipped = outcomes[&qubits[0]];
+                    if flipped { apply(&mut state, "x", qubits); }
+                    apply(&mut state, gate, qubits);
+                    if flipped { apply(&mut state, "x", qubits); }
+                },
+            }
+        }
+        (0..1 << nb_inputs).map(|output| state[output | ancilla_bits]).collect()
+    }).collect()
+}
+
+#[test]
+fn feed_forward_corrects_every_measurement_branch() {
+    set_quiet(true);
+    for name in ["tof_3", "barenco_tof_3"] {
+        let c = Pipeline::parse("fasttmerge,internalhopt").run(bundled(name));
+        let (gadgetized, corrections) = c.hadamard_gadgetization_measured();
+        assert!(!gadgetized.ancillas.is_empty());
+        let optimized = Pipeline::parse("tohpe").run(gadgetized.clone());
+        let expected: Vec<_> = (0..1 << c.nb_qubits).map(|input| run(&c, input)).collect();
+        let ancillas: Vec<usize> = gadgetized.ancillas.keys().copied().collect();
+        for branch in 0..1 << ancillas.len() {
+            let outcomes: HashMap<usize, bool> = ancillas.iter().enumerate().map(|(k, &a)| (a, branch >> k & 1 == 1)).collect();
+            for circuit in [&gadgetized, &optimized] {
+                let states = branch_states(circuit, &corrections, c.nb_qubits, &outcomes);
+                assert!(equal_up_to_scalar(&states, &expected), "{}: outcomes {:?}", name, outcomes);
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use common::{from_qc, random_circuit, run, Amplitude, EPSILON, CLIFFORD_T_GATES};
+use quantum_circuit_optimization::circuit::Circuit;
+use quantum_circuit_optimization::pass::Pipeline;
+use quantum_circuit_optimization::set_quiet;
+use quantum_circuit_optimization::t_opt::Tohpe;
+
+// Action of the circuit on the outputs which are kept, for the computational
+// basis states where the qubits which are not inputs are in the state |0>:
+// for each pair of such states, the partial trace over the discarded qubits
+// of the outer product of their images. Unlike the final states, it does not
+// depend on the global phase, and it determines the action of the circuit on
+// the superpositions of these states.
+ fn kept_action(c: &Circuit, inputs: &[bool], outputs: &[bool]) -> Vec<Vec<Amplitude>> {
+    let input_qubits: Vec<usize> = (0..inputs.len()).filter(|&q| inputs[q]).collect();
+    let states: Vec<Vec<Amplitude>> = (0..1 << input_qubits.len()).map(|bits: usize| {
+        run(c, input_qubits.iter().enumerate().fold(0, |i, (k, &q)| i | (bits >> k & 1) << q))
+    }).collect();
+    let discarded = (0..c.nb_qubits).filter(|&q| !outputs.get(q).copied().unwrap_or(true)).fold(0, |m, q| m | 1 << q);
+    let kept: Vec<usize> = (0..1 << c.nb_qubits).filter(|&i| i & discarded == 0).collect();
+    let mut action = Vec::new();
+    for s1 in &states {
+        for s2 in &states {
+            let mut trace = Vec::new();
+            for &i in &kept {
+                for &j in &kept {
+                    let mut sum = (0.0, 0.0);
+                    for g in (0..1 << c.nb_qubits).filter(|&g| g & !discarded == 0) {
+                        let (a, b) = (s1[i | g], s2[j | g]);
+                        sum.0 += a.0 * b.0 + a.1 * b.1;
+                        sum.1 += a.1 * b.0 - a.0 * b.1;
+                    }
+                    trace.push(sum);
+                }
+            }
+            action.push(trace);
+        }
+    }
+    action
+}
+
+ fn same_action(c1: &Circuit, c2: &Circuit, inputs: &[bool], outputs: &[bool]) -> bool {
+    kept_action(c1, inputs, outputs).iter().flatten().zip(kept_action(c2, inputs, outputs).iter().flatten())
+        .all(|(a, b)| (a.0 - b.0).abs() < EPSILON && (a.1 - b.1).abs() < EPSILON)
+}
+
+#[test]
+fn io_aware_optimization_preserves_the_kept_outputs() {
+    let inputs = [true, true, true, false, false];
+    let outputs = [true, true, true, true, false];
+    let mut removed = 0;
+    for seed in 0..30 {
+        let c = random_circuit(5, 40, &CLIFFORD_T_GATES, seed).decompose_tof();
+        let io_aware = c.propagate_constants(&inputs).remove_discarded(&outputs);
+        assert!(same_action(&io_aware, &c, &inputs, &outputs), "seed {}: constant propagation", seed);
+        let optimized = io_aware.t_opt_io_aware(&Tohpe, "Elimination", &inputs, &outputs);
+        assert!(same_action(&optimized, &c, &inputs, &outputs), "seed {}: T-count optimization", seed);
+        let plain = io_aware.t_opt_with(&Tohpe, "Elimination");
+        assert!(optimized.get_statistics().2 <= plain.get_statistics().2, "seed {}", seed);
+        removed += plain.circ.len().saturating_sub(optimized.circ.len());
+    }
+    assert!(removed > 0);
+}
+
+#[test]
+fn constant_parities_are_folded() {
+    // The qubit c is a copy of a, so that the two T gates form an S gate
+    let c = from_qc(".v a b c\n.i a b\nBEGIN\ncnot a c\nT c\nT a\nH b\ncnot a b\nEND");
+    let (inputs, outputs) = ([true, true, false], [true; 3]);
+    assert_eq!(c.t_opt_with(&Tohpe, "Elimination").get_statistics().2, 2);
+    let optimized = c.t_opt_io_aware(&Tohpe, "Elimination", &inputs, &outputs);
+    assert_eq!(optimized.get_statistics().2, 0);
+    assert!(same_action(&optimized, &c, &inputs, &outputs));
+}
+
+#[test]
+fn garbage_clifford_tails_are_dropped() {
+    let c = random_circuit(4, 12, &CLIFFORD_T_GATES, 10).decompose_tof();
+    let (inputs, outputs) = ([true; 4], [false, true, false, true]);
+    let optimized = c.t_opt_io_aware(&Tohpe, "Elimination", &inputs, &outputs);
+    let plain = c.t_opt_with(&Tohpe, "Elimination").remove_discarded(&outputs);
+    assert!(optimized.circ.len() < plain.circ.len(), "{} gates, {} without the .o line", optimized.circ.len(), plain.circ.len());
+    assert!(same_action(&optimized, &c, &inputs, &outputs));
+}
+
+#[test]
+fn io_aware_optimization_removes_t_gates() {
+    set_quiet(true);
+    let (c, header, map) = Circuit::from_qc("circuits/inputs/csla_mux_3.qc");
+    let (inputs, outputs) = c.io_qubits(&header, &map);
+    let c = Pipeline::parse("fasttmerge,internalhopt,gadgetize").run(c.propagate_constants(&inputs).remove_discarded(&outputs));
+    let plain = c.t_opt_with(&Tohpe, "Elimination").get_statistics().2;
+    let io_aware = c.t_opt_io_aware(&Tohpe, "Elimination", &inputs, &outputs).get_statistics().2;
+    assert!(io_aware < plain, "T-count {} with the .i line, {} without", io_aware, plain);
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::{bundled, equivalent, from_qc};
+use quantum_circuit_optimization::pass::{Cost, Pipeline};
+use quantum_circuit_optimization::{set_quiet, set_recoverable};
+
+ fn names(spec: &str) -> Vec<String> {
+    Pipeline::parse(spec).passes.iter().map(|pass| pass.name()).collect()
+}
+
+#[test]
+fn pipelines_are_parsed() {
+    assert_eq!(names("fasttmerge, internalhopt(synthesis=best),gadgetize(budget=8), loop(fasttodd,internalhopt(synthesis=minimalh),max=5,cost=t>2*h+cnot)"),
+        ["FastTMerge", "InternalHOpt", "Gadgetize", "Loop"]);
+    assert_eq!(names("BBMerge,tohpe(measured),todd(synthesis=Canonical),exact(measured=false),tpar(ancillas=2),schedule(objective=tdepth)"),
+        ["BBMerge", "TOHPE", "TODD", "Exact", "TPar", "Schedule"]);
+    assert_eq!(names("peephole,resynthesize(synthesis=greedy),recycle,,"), ["Peephole", "Resynthesize", "Recycle"]);
+    assert!(names("").is_empty());
+}
+
+#[test]
+fn costs_are_evaluated() {
+    let c = from_qc(".v a b\nBEGIN\nH a\nT a\ncnot a b\nT b\nH a\nEND");
+    assert_eq!(Cost::parse("t>2*h+cnot").evaluate(&c), [2, 5]);
+    assert_eq!(Cost::parse("Qubits+gates>tdepth>ancillas").evaluate(&c), [7, 2, 0]);
+}
+
+#[test]
+fn pipelines_preserve_the_circuit() {
+    set_quiet(true);
+    for name in ["tof_3", "barenco_tof_3", "mod5_4"] {
+        let c = bundled(name);
+        let optimized = Pipeline::parse("fasttmerge,loop(internalhopt(synthesis=best),fasttodd,max=3),peephole,resynthesize,schedule").run(c.clone());
+        assert!(equivalent(&optimized, &c, c.nb_qubits), "{}", name);
+    }
+}
+
+ fn rejects(spec: &str, error: &str) {
+    set_recoverable(true);
+    let message = std::panic::catch_unwind(|| { Pipeline::parse(spec); }).expect_err(spec);
+    assert_eq!(message.downcast_ref::<String>().map(|m| &m[..]), Some(error), "{}", spec);
+}
+
+#[test]
+fn invalid_pipelines_are_rejected() {
+    rejects("gadgetize(budgt=8)", "Parameter not implemented for the pass gadgetize: budgt");
+    rejects("fasttmerge(synthesis=best)", "Parameter not implemented for the pass fasttmerge: synthesis");
+    rejects("loop(fasttodd,mx=5)", "Parameter not implemented for the pass loop: mx");
+    rejects("loop(fasttodd(budget=1))", "Parameter not implemented for the pass fasttodd: budget");
+    rejects("fasttodd(measured=yes)", "Invalid value of the parameter measured: yes");
+    rejects("gadgetize(budget=many)", "Invalid ancilla budget: many");
+    rejects("internalhopt(synthesis=random)", "Clifford synthesis not implemented: random");
+    rejects("schedule(objective=width)", "Scheduling objective not implemented: width");
+    rejects("tmerge(budget=1)", "Pass not implemented: tmerge");
+    rejects("gadgetize(budget=8", "Invalid pass: gadgetize(budget=8");
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::{bundled, equivalent, from_qc, random_circuit, CLIFFORD_T_GATES};
+use quantum_circuit_optimization::circuit::Circuit;
+use quantum_circuit_optimization::peephole::{cancel, peephole, propagate_paulis};
+
+ fn gates(c: &Circuit) -> Vec<String> {
+    c.circ.iter().map(|(gate, qubits)| format!("{} {:?}", gate, qubits)).collect()
+}
+
+#[test]
+fn peephole_preserves_random_circuits() {
+    for seed in 0..100 {
+        let c = random_circuit(4, 40, &CLIFFORD_T_GATES, seed);
+        assert!(equivalent(&propagate_paulis(&c), &c, 4), "propagation, seed {}", seed);
+        assert!(equivalent(&cancel(&c), &c, 4), "cancellation, seed {}", seed);
+        let optimized = peephole(&c);
+        assert!(optimized.circ.len() <= c.circ.len());
+        assert!(equivalent(&optimized, &c, 4), "seed {}", seed);
+    }
+}
+
+#[test]
+fn peephole_preserves_bundled_circuits() {
+    for name in ["tof_3", "barenco_tof_3", "mod5_4", "tof_4", "barenco_tof_4"] {
+        let c = bundled(name);
+        for circuit in [c.clone(), c.decompose_tof()] {
+            let optimized = peephole(&circuit);
+            assert!(equivalent(&optimized, &c, c.nb_qubits), "{}", name);
+        }
+    }
+}
+
+#[test]
+fn gates_are_cancelled_and_merged() {
+    let c = from_qc(".v a b c\nBEGIN\nH a\nH a\ncnot a b\nT a\ncnot a c\nX b\ncnot a b\nT a\nT b\nT* b\ntof a b c\nZ a\ntof a b c\nEND");
+    let optimized = peephole(&c);
+    let mut remaining = gates(&optimized);
+    remaining.sort();
+    assert_eq!(remaining, ["cx [0, 2]", "s [0]", "x [1]", "z [0]"]);
+    assert!(equivalent(&optimized, &c, 3));
+}
+
+#[test]
+fn paulis_are_propagated_to_the_end() {
+    let c = from_qc(".v a b\nBEGIN\nX a\nZ b\ncnot a b\nH b\nS a\nT b\nEND");
+    assert_eq!(gates(&propagate_paulis(&c)), ["cx [0, 1]", "h [1]", "s [0]", "x [1]", "t [1]", "x [0]", "z [1]"]);
+}
+
+#[test]
+fn barriers_stop_the_peephole() {
+    let c = from_qc(".v a b\nBEGIN\nX a\nH b\nmeasure a\nH b\nX a\nif a X b\nX b\nEND");
+    let optimized = peephole(&c);
+    assert_eq!(gates(&optimized), ["x [0]", "measure [0]", "x [0]", "if_x [0, 1]", "x [1]"]);
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::{bundled, equal_up_to_phase, postselected_states};
+use quantum_circuit_optimization::pass::Pipeline;
+use quantum_circuit_optimization::set_quiet;
+
+#[test]
+fn recycling_preserves_the_postselected_circuit() {
+    set_quiet(true);
+    let mut saved = 0;
+    for name in ["tof_3", "tof_4", "barenco_tof_3", "barenco_tof_4"] {
+        for spec in ["fasttmerge,internalhopt,gadgetize", "fasttmerge,internalhopt,gadgetize,fasttodd"] {
+            let c = Pipeline::parse(spec).run(bundled(name));
+            if c.nb_qubits > 11 { continue; }
+            let recycled = c.recycle_ancillas();
+            assert!(recycled.nb_qubits <= c.nb_qubits);
+            assert_eq!(recycled.nb_qubits - recycled.ancillas.len(), c.nb_qubits - c.ancillas.len());
+            let reused = c.ancillas.len() - recycled.ancillas.len();
+            let measurements = recycled.circ.iter().filter(|(gate, _)| gate == "measure").count();
+            assert_eq!(measurements, reused, "{} after {}", name, spec);
+            assert_eq!(recycled.circ.iter().filter(|(gate, _)| gate == "reset").count(), reused);
+            assert!(equal_up_to_phase(&postselected_states(&recycled), &postselected_states(&c)), "{} after {}", name, spec);
+            saved += reused;
+        }
+    }
+    assert!(saved > 0);
+}
+
+#[test]
+fn recycling_measures_and_resets_before_reuse() {
+    let mut c = common::from_qc(".v a b c d\n.i a b\nBEGIN\nH c\ncnot a c\ncnot a c\nH c\nH d\ncnot b d\ncnot b d\nH d\nEND");
+    c.ancillas.insert(2, Some(0));
+    c.ancillas.insert(3, Some(1));
+    let recycled = c.recycle_ancillas();
+    assert_eq!(recycled.nb_qubits, 3);
+    let gates: Vec<&str> = recycled.circ.iter().map(|(gate, _)| &gate[..]).collect();
+    assert_eq!(gates, ["h", "cx", "cx", "h", "measure", "reset", "h", "cx", "cx", "h"]);
+    assert!(recycled.circ[4..10].iter().all(|(_, qubits)| qubits.contains(&2)));
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::{bundled, equivalent, from_qc, random_circuit, CLIFFORD_T_GATES};
+use quantum_circuit_optimization::circuit::Circuit;
+use quantum_circuit_optimization::pass::Pipeline;
+use quantum_circuit_optimization::resynthesis::{resynthesize_cliffords, SYNTHESES};
+use quantum_circuit_optimization::set_quiet;
+
+// Non-Clifford gates acting on each qubit, in their order.
+ fn non_clifford_gates(c: &Circuit) -> Vec<Vec<(String, Vec<usize>)>> {
+    let mut gates = vec![Vec::new(); c.nb_qubits];
+    for (gate, qubits) in c.circ.iter().filter(|(gate, _)| !["h", "x", "z", "s", "cx"].contains(&&gate[..])) {
+        for &q in qubits {
+            gates[q].push((gate.to_string(), qubits.to_vec()));
+        }
+    }
+    gates
+}
+
+// Checks the resynthesized circuit, and returns the number of CNOT and
+// Hadamard gates removed.
+ fn check_resynthesis(c: &Circuit, syntheses: &[&str], name: &str) -> usize {
+    let resynthesized = resynthesize_cliffords(c, syntheses);
+    assert!(equivalent(&resynthesized, c, c.nb_qubits), "{} with {:?}", name, syntheses);
+    assert_eq!(non_clifford_gates(&resynthesized), non_clifford_gates(c), "{} with {:?}", name, syntheses);
+    let (before, after) = (c.stats(), resynthesized.stats());
+    assert!(after.cnot_count <= before.cnot_count, "{}: CNOT count {} -> {}", name, before.cnot_count, after.cnot_count);
+    assert!(after.h_count <= before.h_count, "{}: H-count {} -> {}", name, before.h_count, after.h_count);
+    before.cnot_count + before.h_count - after.cnot_count - after.h_count
+}
+
+#[test]
+fn resynthesis_preserves_random_circuits() {
+    let mut removed = 0;
+    for seed in 0..30 {
+        let c = random_circuit(5, 60, &CLIFFORD_T_GATES, seed);
+        removed += check_resynthesis(&c, &SYNTHESES, &format!("seed {}", seed));
+        check_resynthesis(&c, &["Greedy"], &format!("seed {}", seed));
+    }
+    assert!(removed > 0);
+}
+
+#[test]
+fn resynthesis_preserves_bundled_circuits() {
+    set_quiet(true);
+    for name in ["tof_3", "barenco_tof_3", "mod5_4"] {
+        let c = bundled(name);
+        check_resynthesis(&c, &SYNTHESES, name);
+        let optimized = Pipeline::parse("fasttmerge,internalhopt,tohpe").run(c);
+        check_resynthesis(&optimized, &SYNTHESES, &format!("optimized {}", name));
+    }
+}
+
+#[test]
+fn redundant_clifford_blocks_are_removed() {
+    let c = from_qc(".v a b\nBEGIN\nH a\ncnot a b\nH a\nH a\ncnot a b\nH a\nT a\nT b\nEND");
+    let resynthesized = resynthesize_cliffords(&c, &SYNTHESES);
+    assert_eq!(resynthesized.circ, [("t".to_string(), vec![0]), ("t".to_string(), vec![1])]);
+    assert!(equivalent(&resynthesized, &c, c.nb_qubits));
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::{bundled, equivalent, grid, line};
+use quantum_circuit_optimization::architecture::Architecture;
+use quantum_circuit_optimization::circuit::Circuit;
+use quantum_circuit_optimization::routing::route;
+use quantum_circuit_optimization::set_recoverable;
+
+// The routed circuit only applies CNOT gates between coupled qubits, and acts
+// as the input circuit on the physical qubits given by the placement.
+ fn check_routing(name: &str, arch: &Architecture) {
+    let c = bundled(name);
+    let (routed, placement, added_cnots) = route(&c, arch);
+    assert_eq!(routed.nb_qubits, arch.nb_qubits);
+    assert_eq!(placement.len(), c.nb_qubits);
+    for (gate, qubits) in &routed.circ {
+        if gate == "cx" { assert!(arch.coupling[qubits[0]][qubits[1]], "{}: CNOT between {:?}", name, qubits); }
+    }
+    assert_eq!(routed.cnot_count(), c.decompose_tof().cnot_count() + added_cnots);
+    let mut placed = Circuit::new(arch.nb_qubits);
+    placed.circ = c.circ.iter().map(|(gate, qubits)| (gate.to_string(), qubits.iter().map(|&q| placement[q]).collect())).collect();
+    assert!(equivalent(&routed, &placed, arch.nb_qubits), "{}: routed circuit not equivalent", name);
+}
+
+#[test]
+fn routing_on_a_line() {
+    for name in ["tof_3", "barenco_tof_3", "mod5_4", "tof_4", "barenco_tof_4"] {
+        check_routing(name, &line(7));
+    }
+}
+
+#[test]
+fn routing_on_a_grid() {
+    for name in ["tof_3", "mod5_4", "barenco_tof_4", "tof_5", "barenco_tof_5"] {
+        check_routing(name, &grid(3, 3));
+    }
+}
+
+#[test]
+#[should_panic(expected = "the architecture only has 4")]
+fn routing_requires_enough_physical_qubits() {
+    set_recoverable(true);
+    route(&bundled("tof_3"), &line(4));
+}
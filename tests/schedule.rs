@@ -0,0 +1,94 @@
+mod common;
+
+use common::{bundled, equivalent, random_circuit, CLIFFORD_T_GATES};
+use quantum_circuit_optimization::circuit::Circuit;
+use quantum_circuit_optimization::pass::Pipeline;
+use quantum_circuit_optimization::schedule::{dependencies, schedule, Schedule};
+use quantum_circuit_optimization::set_quiet;
+
+ fn circuits() -> Vec<(String, Circuit)> {
+    set_quiet(true);
+    let mut circuits: Vec<(String, Circuit)> = (0..20).map(|seed| (format!("seed {}", seed), random_circuit(5, 40, &CLIFFORD_T_GATES, seed))).collect();
+    for name in ["tof_3", "barenco_tof_3", "mod5_4"] {
+        circuits.push((name.to_string(), bundled(name)));
+        circuits.push((format!("optimized {}", name), Pipeline::parse("fasttmerge,internalhopt,tohpe").run(bundled(name))));
+    }
+    circuits
+}
+
+#[test]
+fn dependencies_precede_the_gates() {
+    for (name, c) in circuits() {
+        for (i, predecessors) in dependencies(&c).iter().enumerate() {
+            assert!(predecessors.windows(2).all(|w| w[0] < w[1]), "{}: gate {}", name, i);
+            assert!(predecessors.iter().all(|&p| p < i), "{}: gate {}", name, i);
+            // Gates only depend on gates sharing a qubit with them
+            assert!(predecessors.iter().all(|&p| c.circ[p].1.iter().any(|q| c.circ[i].1.contains(q))), "{}: gate {}", name, i);
+        }
+    }
+}
+
+#[test]
+fn schedules_preserve_the_circuit() {
+    for (name, c) in circuits() {
+        for objective in ["depth", "tdepth"] {
+            let moments = Schedule::new(&c, objective).moments;
+            assert_eq!(moments.iter().map(|moment| moment.len()).sum::<usize>(), c.circ.len(), "{}", name);
+            for moment in &moments {
+                let mut qubits: Vec<usize> = moment.iter().flat_map(|(_, q)| q.iter().copied()).collect();
+                let nb_qubits = qubits.len();
+                qubits.sort();
+                qubits.dedup();
+                assert_eq!(qubits.len(), nb_qubits, "{}: the gates of a time step share a qubit", name);
+            }
+            let scheduled = schedule(&c, objective);
+            assert!(equivalent(&scheduled, &c, c.nb_qubits), "{} scheduled for {}", name, objective);
+            let (before, after) = (c.stats(), scheduled.stats());
+            assert!(after.depth <= before.depth, "{}: depth {} -> {}", name, before.depth, after.depth);
+            assert!(after.t_depth <= before.t_depth, "{}: T-depth {} -> {}", name, before.t_depth, after.t_depth);
+            assert_eq!(after.histogram, before.histogram, "{}", name);
+        }
+    }
+}
+
+#[test]
+fn schedules_reduce_the_depth() {
+    // The CNOT gates commute through their shared target, so that the last one
+    // can be applied first
+    let c = common::from_qc(".v a b c d\nBEGIN\ncnot a b\ncnot b c\ncnot d c\nEND");
+    let scheduled = schedule(&c, "depth");
+    assert_eq!((c.depth(), scheduled.depth()), (3, 2));
+    assert!(equivalent(&scheduled, &c, c.nb_qubits));
+}
+
+#[test]
+fn asap_moments_match_the_depth() {
+    for (name, c) in circuits() {
+        let asap = Schedule::asap(&c);
+        assert_eq!(asap.moments.len(), c.depth(), "{}", name);
+        assert!(equivalent(&asap.to_circ(), &c, c.nb_qubits), "{}", name);
+    }
+}
+
+#[test]
+fn moments_can_be_read_back() {
+    for (name, c) in circuits() {
+        let asap = Schedule::asap(&c).to_circ();
+        let header = format!(".v {}", (1..=c.nb_qubits).map(|q| q.to_string()).collect::<Vec<_>>().join(" "));
+        let map = (0..c.nb_qubits).map(|q| (q, (q + 1).to_string())).collect();
+        let mut qc = Vec::new();
+        c.write_qc_with(&mut qc, header, map, true);
+        let qc = String::from_utf8(qc).unwrap();
+        assert_eq!(qc.lines().filter(|line| line.starts_with("# Moment")).count(), c.depth(), "{}", name);
+        let (read, _, _) = Circuit::read_qc(qc.as_bytes());
+        assert_eq!(read.decompose_tof().circ, asap.decompose_tof().circ, "{}", name);
+
+        let mut qasm = Vec::new();
+        c.write_qasm_with(&mut qasm, true);
+        let qasm = String::from_utf8(qasm).unwrap();
+        assert_eq!(qasm.lines().filter(|line| line.starts_with("// Moment")).count(), c.depth(), "{}", name);
+        // The CCZ gates are written as Toffoli gates between Hadamard gates
+        let (read, _, _) = Circuit::read_qasm(&qasm);
+        assert!(equivalent(&read, &c, c.nb_qubits), "{}", name);
+    }
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use serde_json::{json, Value};
+use quantum_circuit_optimization::circuit::Circuit;
+use quantum_circuit_optimization::server::Server;
+use quantum_circuit_optimization::{set_quiet, set_recoverable, silence_failures};
+
+ fn request(server: &mut Server, request: Value) -> Value {
+    set_quiet(true);
+    set_recoverable(true);
+    silence_failures();
+    let (response, shutdown) = server.handle(&request.to_string());
+    assert!(!shutdown);
+    assert_eq!(response["jsonrpc"], "2.0");
+    assert_eq!(response["id"], request["id"]);
+    response
+}
+
+ fn optimize(id: u64, params: Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "method": "optimize", "params": params})
+}
+
+#[test]
+fn well_formed_requests_are_optimized() {
+    let mut server = Server::new();
+    let text = std::fs::read_to_string("circuits/inputs/tof_3.qc").unwrap();
+    let response = request(&mut server, optimize(1, json!({"circuit": text, "pipeline": "fasttmerge,internalhopt,tohpe", "output_format": "qasm"})));
+    let result = &response["result"];
+    assert!(response.get("error").is_none(), "{}", response);
+    assert_eq!(result["format"], "qasm");
+    let (c, _, _) = Circuit::read_qasm(result["circuit"].as_str().unwrap());
+    assert_eq!(result["statistics"]["input"]["t_count"], 21);
+    assert_eq!(result["statistics"]["optimized"]["t_count"], c.get_statistics().2);
+    assert!(c.get_statistics().2 < 21);
+    // The same pipeline is reused by the next request
+    let response = request(&mut server, optimize(2, json!({"circuit": text, "pipeline": "fasttmerge,internalhopt,tohpe"})));
+    assert_eq!(response["result"]["format"], "qc");
+    assert_eq!(response["result"]["statistics"]["optimized"]["t_count"], c.get_statistics().2);
+}
+
+#[test]
+fn malformed_requests_are_rejected() {
+    let mut server = Server::new();
+    let (response, shutdown) = server.handle("{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": ");
+    assert!(!shutdown);
+    assert_eq!(response["id"], Value::Null);
+    assert_eq!(response["error"]["code"], -32700);
+    assert!(response["error"]["message"].as_str().unwrap().starts_with("Parse error"));
+    let response = request(&mut server, json!({"jsonrpc": "2.0", "id": 2, "method": "frobnicate"}));
+    assert_eq!(response["error"]["code"], -32601);
+    assert_eq!(response["error"]["message"], "Method not found: frobnicate");
+    let response = request(&mut server, optimize(3, json!({"pipeline": "tohpe"})));
+    assert_eq!(response["error"]["code"], -32602);
+    assert_eq!(response["error"]["message"], "Missing circuit");
+}
+
+#[test]
+fn unknown_passes_are_reported() {
+    let mut server = Server::new();
+    let text = std::fs::read_to_string("circuits/inputs/tof_3.qc").unwrap();
+    let response = request(&mut server, optimize(1, json!({"circuit": text, "pipeline": "fasttmerge,frobnicate"})));
+    assert_eq!(response["error"]["code"], -32000);
+    assert_eq!(response["error"]["message"], "Pass not implemented: frobnicate");
+    // The server keeps answering after the error
+    let response = request(&mut server, optimize(2, json!({"circuit": text, "pipeline": "fasttmerge"})));
+    assert!(response.get("result").is_some(), "{}", response);
+}
+
+#[test]
+fn limits_are_enforced() {
+    let mut server = Server::new();
+    let text = std::fs::read_to_string("circuits/inputs/tof_3.qc").unwrap();
+    let response = request(&mut server, optimize(1, json!({"circuit": text, "limits": {"max_qubits": 4}})));
+    assert_eq!(response["error"]["code"], -32602);
+    assert_eq!(response["error"]["message"], "The circuit uses 5 qubits, more than the limit of 4");
+    let response = request(&mut server, optimize(2, json!({"circuit": text, "limits": {"max_gates": 3}})));
+    assert_eq!(response["error"]["code"], -32602);
+    assert!(response["error"]["message"].as_str().unwrap().ends_with("gates, more than the limit of 3"));
+    let response = request(&mut server, optimize(3, json!({"circuit": text, "limits": {"max_qubits": "five"}})));
+    assert_eq!(response["error"]["message"], "Invalid limit: max_qubits");
+    let response = request(&mut server, optimize(4, json!({"circuit": text, "limits": {"max_qubits": 5, "max_gates": 1000}})));
+    assert!(response.get("result").is_some(), "{}", response);
+}
+
+#[test]
+fn shutdown_stops_the_server() {
+    let mut server = Server::new();
+    let (response, shutdown) = server.handle(&json!({"jsonrpc": "2.0", "id": 7, "method": "shutdown"}).to_string());
+    assert!(shutdown);
+    assert_eq!(response, json!({"jsonrpc": "2.0", "id": 7, "result": null}));
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::{bundled, equivalent, random_circuit, run, Amplitude, Random, EPSILON, CLIFFORD_T_GATES};
+use quantum_circuit_optimization::circuit::Circuit;
+use quantum_circuit_optimization::t_opt::Tohpe;
+use quantum_circuit_optimization::pass::Pipeline;
+use quantum_circuit_optimization::set_quiet;
+
+// Bundled circuits on which TODD runs in a few seconds.
+const CIRCUITS: [&str; 12] = ["tof_3", "tof_4", "tof_5", "barenco_tof_3", "barenco_tof_4", "barenco_tof_5",
+    "mod5_4", "gf2^4_mult", "mod_mult_55", "vbe_adder_3", "rc_adder_6", "mod_red_21"];
+
+ fn t_count(name: &str, spec: &str) -> usize {
+    set_quiet(true);
+    Pipeline::parse(spec).run(bundled(name)).stats().t_count
+}
+
+#[test]
+fn todd_is_never_worse_than_tohpe() {
+    for name in CIRCUITS {
+        let tohpe = t_count(name, "fasttmerge,internalhopt,gadgetize,tohpe");
+        let todd = t_count(name, "fasttmerge,internalhopt,gadgetize,todd");
+        assert!(todd <= tohpe, "{}: TODD {} > TOHPE {}", name, todd, tohpe);
+    }
+}
+
+#[test]
+fn optimizers_preserve_the_circuit() {
+    set_quiet(true);
+    for name in ["tof_3", "barenco_tof_3", "mod5_4"] {
+        let c = bundled(name);
+        for optimizer in ["tohpe", "todd", "fasttodd", "exact"] {
+            let optimized = Pipeline::parse(&format!("fasttmerge,internalhopt,{}", optimizer)).run(c.clone());
+            assert_eq!(optimized.nb_qubits, c.nb_qubits);
+            assert!(equivalent(&optimized, &c, c.nb_qubits), "{} on {}", optimizer, name);
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "rank at most 6")]
+fn exact_rejects_large_ranks() {
+    quantum_circuit_optimization::set_recoverable(true);
+    set_quiet(true);
+    Pipeline::parse("fasttmerge,internalhopt,gadgetize,exact").run(bundled("gf2^4_mult"));
+}
+
+// Whether the first circuit is the second one followed by a diagonal operator:
+// on each computational basis state, the ratio of the amplitudes of an output
+// basis state in the two circuits has modulus 1 and only depends on the output.
+ fn equal_up_to_diagonal(c1: &Circuit, c2: &Circuit) -> bool {
+    let mut diagonal: Vec<Option<Amplitude>> = vec![None; 1 << c1.nb_qubits];
+    for input in 0..1 << c1.nb_qubits {
+        for (k, (a, b)) in run(c1, input).into_iter().zip(run(c2, input)).enumerate() {
+            let norm = b.0 * b.0 + b.1 * b.1;
+            if ((a.0 * a.0 + a.1 * a.1).sqrt() - norm.sqrt()).abs() > EPSILON { return false; }
+            if norm < EPSILON { continue; }
+            let ratio = ((a.0 * b.0 + a.1 * b.1) / norm, (a.1 * b.0 - a.0 * b.1) / norm);
+            match diagonal[k] {
+                None => diagonal[k] = Some(ratio),
+                Some(d) if (d.0 - ratio.0).abs() > EPSILON || (d.1 - ratio.1).abs() > EPSILON => return false,
+                Some(_) => {},
+            }
+        }
+    }
+    true
+}
+
+// Probabilities of the computational basis outcomes on the input state with
+// the given amplitudes on the computational basis states.
+ fn probabilities(c: &Circuit, input: &[Amplitude]) -> Vec<f64> {
+    let mut state = vec![(0.0, 0.0); 1 << c.nb_qubits];
+    for (i, a) in input.iter().enumerate() {
+        for (s, b) in state.iter_mut().zip(run(c, i)) {
+            s.0 += a.0 * b.0 - a.1 * b.1;
+            s.1 += a.0 * b.1 + a.1 * b.0;
+        }
+    }
+    state.iter().map(|a| a.0 * a.0 + a.1 * a.1).collect()
+}
+
+#[test]
+fn measured_outputs_are_preserved_up_to_a_diagonal() {
+    let mut random = Random::new(3);
+    for seed in 0..20 {
+        let c = random_circuit(4, 40, &CLIFFORD_T_GATES, seed).decompose_tof();
+        let optimized = c.t_opt_measured(&Tohpe, "Elimination");
+        assert!(optimized.get_statistics().2 <= c.t_opt(&Tohpe).get_statistics().2, "seed {}", seed);
+        assert!(equal_up_to_diagonal(&optimized, &c), "seed {}", seed);
+        for _ in 0..5 {
+            let input: Vec<Amplitude> = (0..1 << c.nb_qubits).map(|_| (random.next(201) as f64 / 100.0 - 1.0, random.next(201) as f64 / 100.0 - 1.0)).collect();
+            for (p, q) in probabilities(&optimized, &input).iter().zip(probabilities(&c, &input)) {
+                assert!((p - q).abs() < EPSILON, "seed {}", seed);
+            }
+        }
+    }
+}
+
+#[test]
+fn measured_outputs_reduce_the_t_count() {
+    set_quiet(true);
+    assert_eq!(t_count("mod5_4", "fasttmerge,internalhopt,gadgetize,tohpe"), 7);
+    assert_eq!(t_count("mod5_4", "fasttmerge,internalhopt,gadgetize,tohpe(measured)"), 4);
+    let c = Pipeline::parse("fasttmerge,internalhopt").run(bundled("mod5_4"));
+    assert!(equal_up_to_diagonal(&Pipeline::parse("tohpe(measured)").run(c.clone()), &c));
+}
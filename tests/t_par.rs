@@ -0,0 +1,94 @@
+mod common;
+
+use common::{bundled, equivalent, random_circuit, CLIFFORD_T_GATES};
+use quantum_circuit_optimization::bit_vector::BitVector;
+use quantum_circuit_optimization::circuit::{Circuit, SlicedCircuit};
+use quantum_circuit_optimization::phase_polynomial::PhasePolynomial;
+
+// Checks that the T-depth optimization with the given number of ancillas
+// implements the circuit, the ancillas being left in the state |0>, and does
+// not increase the T-depth. The layered phase polynomials keep the T-count,
+// which can only be reduced by the final peephole optimization.
+ fn check_t_par(c: &Circuit, nb_ancillas: usize, name: &str) {
+    let reference = c.decompose_tof();
+    let optimized = c.t_par(nb_ancillas, "Elimination");
+    assert_eq!(optimized.nb_qubits, c.nb_qubits + nb_ancillas, "{}", name);
+    assert_eq!(optimized.ancillas.len(), nb_ancillas, "{}", name);
+    assert!(optimized.ancillas.iter().all(|(&a, parent)| a >= c.nb_qubits && parent.is_none()), "{}", name);
+    let mut padded = reference.clone();
+    padded.nb_qubits += nb_ancillas;
+    assert!(equivalent(&optimized, &padded, c.nb_qubits), "{} with {} ancillas", name, nb_ancillas);
+    let (before, after) = (reference.stats(), optimized.stats());
+    let layered = SlicedCircuit::from_circ(&reference).t_par(nb_ancillas, "Elimination");
+    assert_eq!(layered.stats().t_count, before.t_count, "{} with {} ancillas", name, nb_ancillas);
+    assert!(after.t_count <= before.t_count, "{} with {} ancillas", name, nb_ancillas);
+    assert!(after.t_depth <= before.t_depth, "{} with {} ancillas: T-depth {} -> {}", name, nb_ancillas, before.t_depth, after.t_depth);
+}
+
+#[test]
+fn t_par_preserves_random_circuits() {
+    for seed in 0..20 {
+        let c = random_circuit(4, 40, &CLIFFORD_T_GATES, seed);
+        for nb_ancillas in 0..3 {
+            check_t_par(&c, nb_ancillas, &format!("seed {}", seed));
+        }
+    }
+}
+
+#[test]
+fn t_par_preserves_bundled_circuits() {
+    for name in ["tof_3", "barenco_tof_3", "mod5_4"] {
+        let c = bundled(name);
+        for nb_ancillas in 0..3 {
+            check_t_par(&c, nb_ancillas, name);
+        }
+    }
+}
+
+#[test]
+fn t_par_reduces_the_t_depth() {
+    // Seven T gates on parities of a, b and c, which fit in 3 layers without
+    // ancillas and in a single layer with 4 ancillas
+    let c = common::from_qc(".v a b c\nBEGIN\nT a\nT b\nT c\ncnot a b\nT b\ncnot b c\nT c\ncnot a b\nT b\ncnot b c\nT c\nEND");
+    assert_eq!(c.stats().t_depth, 4);
+    assert_eq!(c.t_par(0, "Elimination").stats().t_depth, 3);
+    assert_eq!(c.t_par(4, "Elimination").stats().t_depth, 1);
+    check_t_par(&c, 4, "parities");
+}
+
+#[test]
+fn layers_partition_the_terms() {
+    let n = 4;
+    let mut p = PhasePolynomial::new(n);
+    for bits in 1..1 << n {
+        let mut z = BitVector::new(n);
+        for q in (0..n).filter(|q| bits >> q & 1 == 1) { z.xor_bit(q); }
+        p.table.push(z);
+    }
+    for nb_ancillas in 0..4 {
+        let layers = p.layers(nb_ancillas);
+        assert_eq!(layers.iter().map(|layer| layer.len()).sum::<usize>(), p.table.len());
+        for layer in &layers {
+            assert!(layer.len() <= n + nb_ancillas);
+            // Each term beyond the rank of the layer uses an ancilla
+            let mut rank = 0;
+            let mut rows: Vec<BitVector> = Vec::new();
+            for z in layer {
+                let mut r = z.clone();
+                for row in &rows {
+                    if r.get(row.get_first_one()) { r.xor(row); }
+                }
+                if r.popcount() != 0 {
+                    rank += 1;
+                    for row in rows.iter_mut() {
+                        if row.get(r.get_first_one()) { row.xor(&r); }
+                    }
+                    rows.push(r);
+                }
+            }
+            assert!(layer.len() - rank <= nb_ancillas, "{} terms of rank {} with {} ancillas", layer.len(), rank, nb_ancillas);
+        }
+        assert!(layers.len() * (n + nb_ancillas) >= p.table.len());
+    }
+    assert_eq!(p.layers(0).len(), 4);
+}
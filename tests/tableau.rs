@@ -0,0 +1,37 @@
+mod common;
+
+use common::{equivalent, random_circuit, run, equal_up_to_phase, to_tableau, CLIFFORD_GATES};
+use quantum_circuit_optimization::circuit::Circuit;
+
+const SYNTHESES: [&str; 5] = ["Elimination", "Greedy", "Canonical", "Best", "MinimalH"];
+
+#[test]
+fn syntheses_implement_the_tableau() {
+    for seed in 0..20 {
+        let c = random_circuit(5, 60, &CLIFFORD_GATES, seed);
+        let tab = to_tableau(&c);
+        for synthesis in SYNTHESES {
+            assert!(equivalent(&tab.to_circ_with(false, synthesis), &c, 5), "{} synthesis, seed {}", synthesis, seed);
+            let mut identity = c.clone();
+            identity.append(tab.to_circ_with(true, synthesis).circ);
+            let states: Vec<_> = (0..1 << 5).map(|i| run(&identity, i)).collect();
+            let inputs: Vec<_> = (0..1 << 5).map(|i| run(&Circuit::new(5), i)).collect();
+            assert!(equal_up_to_phase(&states, &inputs), "inverse {} synthesis, seed {}", synthesis, seed);
+        }
+    }
+}
+
+#[test]
+fn minimal_h_and_best_syntheses() {
+    for seed in 0..50 {
+        let tab = to_tableau(&random_circuit(6, 80, &CLIFFORD_GATES, seed));
+        for inverse in [false, true] {
+            let circuits: Vec<Circuit> = SYNTHESES.iter().map(|s| tab.to_circ_with(inverse, s)).collect();
+            let h_counts: Vec<usize> = circuits.iter().map(|c| c.get_statistics().0).collect();
+            let cnot_counts: Vec<usize> = circuits.iter().map(|c| c.cnot_count()).collect();
+            assert_eq!(h_counts[4], tab.min_h_count(), "seed {}", seed);
+            assert_eq!(h_counts[4], *h_counts.iter().min().unwrap(), "seed {}", seed);
+            assert_eq!(cnot_counts[3], *cnot_counts.iter().min().unwrap(), "seed {}", seed);
+        }
+    }
+}